@@ -22,6 +22,10 @@ impl FunctionRegistry {
         self.functions.get(name)
     }
 
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.functions.keys()
+    }
+
     pub fn has(&self, name: &str) -> bool {
         self.functions.contains_key(name)
     }
@@ -0,0 +1,1020 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language::binder::FunctionRegistry;
+use crate::language::datatypes::{BUILTIN_GENERICS, DataTypeType};
+use crate::language::errors::LangError;
+use crate::language::expressions::{ClassDecl, Expression, FieldDecl, Literal, Signature, Visibility};
+use crate::language::span::Span;
+
+// what is known about a module without running it
+struct ModuleShape {
+    members: Vec<String>,
+    exports: Vec<String>,
+    modules: HashMap<String, ModuleShape>,
+}
+
+struct MethodShape {
+    name: String,
+    ty: DataTypeType, // always a function type
+    type_params: Vec<String>, // the class's and the method's own
+    is_static: bool,
+    visibility: Visibility,
+}
+
+struct ClassShape {
+    type_params: Vec<String>,
+    base: Option<String>,
+    fields: Vec<FieldDecl>,
+    methods: Vec<MethodShape>,
+}
+
+struct Variable {
+    ty: DataTypeType,
+    annotated: bool, // annotated variables keep their type, the others take whatever is assigned last
+}
+
+// the function whose body is being checked
+struct FunctionContext {
+    name: String,
+    returns: Option<DataTypeType>,
+    returned: Vec<DataTypeType>,
+}
+
+// checks over the parsed program for `check`, a normal run never calls it and does not enforce annotations.
+// types that cannot be inferred are `any` and never reported, the runtime checks still cover them
+pub struct Checker<'a> {
+    natives: &'a FunctionRegistry,
+    modules: HashMap<String, ModuleShape>,
+    classes: HashMap<String, ClassShape>,
+    type_arity: HashMap<String, usize>, // every type an annotation may name and how many parameters it takes
+    enums: Vec<String>,
+    type_params: Vec<String>, // of the generic classes and functions being checked
+    variables: Vec<HashMap<String, Variable>>,
+    functions: Vec<FunctionContext>,
+    // names a function body assigns before they are declared, their type depends on when the function runs
+    assigned_later: HashSet<String>,
+    class_context: Option<String>, // class whose methods are being checked
+    diagnostics: Vec<LangError>,
+}
+
+impl ModuleShape {
+    fn from_body(body: &[(Expression, Span)]) -> Self {
+        let mut modules = HashMap::new();
+        for (expr, _) in body {
+            if let Some((name, shape)) = ModuleShape::declared_by(expr) {
+                modules.insert(name, shape);
+            }
+        }
+
+        ModuleShape {
+            members: body.iter().filter_map(|(expr, _)| expr.declared_name().map(str::to_string)).collect(),
+            exports: Expression::exports(body).unwrap_or_default(),
+            modules,
+        }
+    }
+
+    fn declared_by(expr: &Expression) -> Option<(String, ModuleShape)> {
+        match expr {
+            Expression::ModuleDeclaration(name, body) => Some((name.clone(), ModuleShape::from_body(body))),
+            Expression::Import(name, _, body) => Some((name.clone(), ModuleShape::from_body(body))),
+            Expression::Modified(_, declaration) => ModuleShape::declared_by(declaration),
+            _ => None,
+        }
+    }
+}
+
+impl ClassShape {
+    fn from_decl(decl: &ClassDecl) -> Self {
+        ClassShape {
+            type_params: decl.type_params.clone(),
+            base: decl.base.clone(),
+            fields: decl.fields.clone(),
+            methods: decl
+                .methods
+                .iter()
+                .map(|method| MethodShape {
+                    name: method.name.clone(),
+                    ty: function_type(&method.signature),
+                    type_params: decl.type_params.iter().chain(&method.signature.type_params).cloned().collect(),
+                    is_static: method.is_static,
+                    visibility: method.visibility,
+                })
+                .collect(),
+        }
+    }
+}
+
+// the one type all of `types` share, `any` when they differ or there are none
+fn join_all(types: &[DataTypeType]) -> DataTypeType {
+    match types.split_first() {
+        Some((first, rest)) => rest.iter().fold(first.clone(), |joined, ty| joined.join(ty)),
+        None => DataTypeType::Any,
+    }
+}
+
+// `ty` with the type parameters in `bindings` replaced
+fn substitute(ty: &DataTypeType, bindings: &HashMap<String, DataTypeType>) -> DataTypeType {
+    match ty {
+        DataTypeType::Param(name) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        DataTypeType::Named(name, params) => DataTypeType::Named(name.clone(), params.iter().map(|param| substitute(param, bindings)).collect()),
+        DataTypeType::Function(params, returns) => DataTypeType::Function(
+            params.as_ref().map(|params| params.iter().map(|param| substitute(param, bindings)).collect()),
+            Box::new(substitute(returns, bindings)),
+        ),
+        other => other.clone(),
+    }
+}
+
+// type parameters nothing was inferred for are `any` to the caller
+fn erase(ty: &DataTypeType) -> DataTypeType {
+    match ty {
+        DataTypeType::Param(_) => DataTypeType::Any,
+        DataTypeType::Named(name, params) => DataTypeType::Named(name.clone(), params.iter().map(erase).collect()),
+        DataTypeType::Function(params, returns) => {
+            DataTypeType::Function(params.as_ref().map(|params| params.iter().map(erase).collect()), Box::new(erase(returns)))
+        },
+        other => other.clone(),
+    }
+}
+
+// binds the type parameters in `param` to what `arg` has in their place, the first binding of each wins
+fn unify(param: &DataTypeType, arg: &DataTypeType, bindings: &mut HashMap<String, DataTypeType>) {
+    match (param, arg) {
+        (DataTypeType::Param(name), arg) if arg.is_known() => {
+            bindings.entry(name.clone()).or_insert_with(|| arg.clone());
+        },
+        (DataTypeType::Named(expected, params), DataTypeType::Named(actual, args)) if expected == actual && params.len() == args.len() => {
+            for (param, arg) in params.iter().zip(args) {
+                unify(param, arg, bindings);
+            }
+        },
+        (DataTypeType::Function(params, returns), DataTypeType::Function(args, arg_returns)) => {
+            if let (Some(params), Some(args)) = (params, args) {
+                for (param, arg) in params.iter().zip(args) {
+                    unify(param, arg, bindings);
+                }
+            }
+            unify(returns, arg_returns, bindings);
+        },
+        _ => {},
+    }
+}
+
+// unannotated parameters and returns are `any`
+fn function_type(signature: &Signature) -> DataTypeType {
+    let params = signature.params.iter().map(|param| param.clone().unwrap_or(DataTypeType::Any)).collect();
+    DataTypeType::Function(Some(params), Box::new(signature.returns.clone().unwrap_or(DataTypeType::Any)))
+}
+
+impl<'a> Checker<'a> {
+    pub fn check(statements: &[(Expression, Span)], natives: &'a FunctionRegistry) -> Vec<LangError> {
+        let mut checker = Checker {
+            natives,
+            modules: HashMap::new(),
+            classes: HashMap::new(),
+            type_arity: BUILTIN_GENERICS.iter().map(|(name, arity)| (name.to_string(), *arity)).collect(),
+            enums: vec!["Option".to_string(), "Result".to_string()],
+            type_params: Vec::new(),
+            variables: vec![HashMap::new()],
+            functions: Vec::new(),
+            assigned_later: HashSet::new(),
+            class_context: None,
+            diagnostics: Vec::new(),
+        };
+
+        // classes and modules can be used above their declaration, from inside functions
+        for (expr, _) in statements {
+            checker.declare(expr);
+            checker.collect_types(expr);
+        }
+        for (expr, span) in statements {
+            checker.visit(expr, *span);
+        }
+
+        checker.diagnostics
+    }
+
+    fn declare(&mut self, expr: &Expression) {
+        match expr {
+            Expression::ClassDeclaration(decl) => {
+                self.classes.insert(decl.name.clone(), ClassShape::from_decl(decl));
+            },
+            Expression::Modified(_, declaration) => self.declare(declaration),
+            _ => {
+                if let Some((name, shape)) = ModuleShape::declared_by(expr) {
+                    self.modules.insert(name, shape);
+                }
+            },
+        }
+    }
+
+    // classes and enums anywhere in the file, nested modules and imports included
+    fn collect_types(&mut self, expr: &Expression) {
+        match expr {
+            Expression::ClassDeclaration(decl) => {
+                self.type_arity.insert(decl.name.clone(), decl.type_params.len());
+            },
+            Expression::EnumDeclaration(name, _) => {
+                self.type_arity.insert(name.clone(), 0);
+                self.enums.push(name.clone());
+            },
+            Expression::ModuleDeclaration(_, body) => body.iter().for_each(|(expr, _)| self.collect_types(expr)),
+            Expression::Import(_, _, body) => body.iter().for_each(|(expr, _)| self.collect_types(expr)),
+            Expression::Modified(_, declaration) => self.collect_types(declaration),
+            _ => {},
+        }
+    }
+
+    fn report(&mut self, message: String, span: Span) {
+        self.diagnostics.push(LangError::at(message, span));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn declare_variable(&mut self, name: &str, ty: DataTypeType, annotated: bool) {
+        let scope = self.variables.last_mut().expect("checker scopes are never empty");
+        scope.insert(name.to_string(), Variable { ty, annotated });
+    }
+
+    // the inferred type of `expr`, reporting what is wrong along the way
+    fn visit(&mut self, expr: &Expression, span: Span) -> DataTypeType {
+        match expr {
+            Expression::Literal(Literal::Number(_)) => DataTypeType::Float,
+            Expression::Literal(Literal::Bool(_)) => DataTypeType::Bool,
+            Expression::Literal(Literal::String(_)) => DataTypeType::String,
+            Expression::Identifier(name) => self.lookup(name).map(|var| var.ty.clone()).unwrap_or(DataTypeType::Any),
+            Expression::Declaration(name, annotation) => {
+                let annotated = annotation.is_some();
+                let annotation = self.annotation(annotation, span);
+                self.declare_variable(name, annotation.unwrap_or(DataTypeType::Any), annotated);
+                DataTypeType::Any
+            },
+            Expression::Interpolation(segments) => {
+                for segment in segments {
+                    self.visit(segment, span);
+                }
+                DataTypeType::String
+            },
+            Expression::Array(elements) => {
+                let types: Vec<DataTypeType> = elements.iter().map(|element| self.visit(element, span)).collect();
+                DataTypeType::array(join_all(&types))
+            },
+            Expression::Map(entries) => {
+                let types: Vec<DataTypeType> = entries.iter().map(|(_, value)| self.visit(value, span)).collect();
+                DataTypeType::map(join_all(&types))
+            },
+            Expression::Operation(op, tree) if op == "=" => self.visit_assignment(&tree[0], &tree[1], span),
+            Expression::CompoundAssignment(op, target, value) => self.visit_compound(op, target, value, span),
+            Expression::Operation(op, tree) => {
+                let lhs = self.visit(&tree[0], span);
+                let rhs = self.visit(&tree[1], span);
+                self.binary(op, &lhs, &rhs, span)
+            },
+            Expression::Unary(op, operand) => {
+                let operand = self.visit(operand, span);
+                let expected = if op == "!" { DataTypeType::Bool } else { DataTypeType::Float };
+                if operand.is_known() && operand != expected {
+                    self.report(format!("Unary '{}' expects a {}, got {}", op, expected, operand), span);
+                }
+                expected
+            },
+            Expression::Index(target, index) => {
+                let target = self.visit(target, span);
+                self.visit(index, span);
+                match target.element() {
+                    Some(element) => element,
+                    None if target == DataTypeType::String => DataTypeType::String,
+                    None if !target.is_known() || matches!(target, DataTypeType::Named(..)) => DataTypeType::Any,
+                    None => {
+                        self.report(format!("Cannot index a value of type {}", target), span);
+                        DataTypeType::Any
+                    },
+                }
+            },
+            Expression::Slice(target, start, end) => {
+                let target = self.visit(target, span);
+                for bound in [start, end].into_iter().flatten() {
+                    self.visit(bound, span);
+                }
+                match target {
+                    DataTypeType::Named(ref name, _) if name == "Array" => target,
+                    DataTypeType::String | DataTypeType::Any | DataTypeType::Param(_) => target,
+                    other => {
+                        self.report(format!("Cannot slice a value of type {}", other), span);
+                        DataTypeType::Any
+                    },
+                }
+            },
+            Expression::FunctionCall(callee, args) => {
+                let args: Vec<DataTypeType> = args.iter().map(|arg| self.visit(arg, span)).collect();
+                self.visit_call(callee, &args, span)
+            },
+            Expression::FunctionDeclaration(name, params, body, signature) => {
+                // declared before the body is checked so it can call itself
+                let type_params = [self.type_params.as_slice(), &signature.type_params].concat();
+                self.declare_variable(name, self.resolve(&function_type(signature), &type_params), false);
+                let ty = self.visit_function(name, params, body, signature, None, span);
+                self.declare_variable(name, ty, false);
+                DataTypeType::Any
+            },
+            Expression::Closure(params, body, signature) => self.visit_function("<closure>", params, body, signature, None, span),
+            Expression::EnumDeclaration(..) => DataTypeType::Any,
+            Expression::Member(target, name) => self.visit_member(target, name, span),
+            Expression::ClassDeclaration(decl) => {
+                self.classes.entry(decl.name.clone()).or_insert_with(|| ClassShape::from_decl(decl));
+                let outer_params = self.type_params.len();
+                self.type_params.extend(decl.type_params.iter().cloned());
+                for field in &decl.fields {
+                    self.annotation(&field.annotation, span);
+                }
+
+                let previous = self.class_context.replace(decl.name.clone());
+                // inside its methods an instance is generic over the class's own parameters
+                let this = DataTypeType::Named(decl.name.clone(), decl.type_params.iter().cloned().map(DataTypeType::Param).collect());
+                for method in &decl.methods {
+                    let receiver = (!method.is_static).then(|| this.clone());
+                    let qualified = format!("{}::{}", decl.name, method.name);
+                    self.visit_function(&qualified, &method.params, &method.body, &method.signature, receiver, span);
+                }
+                self.class_context = previous;
+                self.type_params.truncate(outer_params);
+                DataTypeType::Any
+            },
+            Expression::StructLiteral(class_name, entries) => {
+                // the type arguments of a generic class come from its field values
+                let mut bindings = HashMap::new();
+                for (field, value) in entries {
+                    let value = self.visit(value, span);
+                    let Some(expected) = self.declared_field_type(class_name, field) else {
+                        continue;
+                    };
+
+                    unify(&expected, &value, &mut bindings);
+                    let expected = substitute(&expected, &bindings);
+                    if !self.fits(&expected, &value) {
+                        self.report(format!("Field '{}' of '{}' is {}, got {}", field, class_name, expected, value), span);
+                    }
+                }
+
+                match self.classes.get(class_name) {
+                    Some(class) => {
+                        let args = class.type_params.iter().map(|param| bindings.get(param).cloned().unwrap_or(DataTypeType::Any));
+                        DataTypeType::Named(class_name.clone(), args.collect())
+                    },
+                    None => DataTypeType::Any,
+                }
+            },
+            Expression::ModuleDeclaration(_, body) => {
+                if let Err(err) = Expression::exports(body) {
+                    self.diagnostics.push(err.or_at(span));
+                }
+
+                self.variables.push(HashMap::new());
+                for (expr, span) in body {
+                    self.visit(expr, *span);
+                }
+                self.variables.pop();
+                DataTypeType::Any
+            },
+            // an imported file has positions of its own, it is checked on its own against its source
+            Expression::Import(..) => DataTypeType::Any,
+            Expression::Using(target) | Expression::InfiniteLoop(target) => {
+                self.visit(target, span);
+                DataTypeType::Any
+            },
+            Expression::Modified(_, declaration) => self.visit(declaration, span),
+            Expression::Return(value) => {
+                let value = self.visit(value, span);
+                self.visit_return(value, span);
+                DataTypeType::Any
+            },
+            Expression::If(condition, body, branches, otherwise) => {
+                self.visit(condition, span);
+                self.visit(body, span);
+                for (condition, body) in branches {
+                    self.visit(condition, span);
+                    self.visit(body, span);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.visit(otherwise, span);
+                }
+                DataTypeType::Any
+            },
+            Expression::Block(body) => {
+                self.variables.push(HashMap::new());
+                for (expr, span) in body {
+                    self.visit(expr, *span);
+                }
+                self.variables.pop();
+                DataTypeType::Any
+            },
+            Expression::ForLoop(var_name, start, end, step, body) => {
+                for bound in [Some(start), Some(end), step.as_ref()].into_iter().flatten() {
+                    let bound = self.visit(bound, span);
+                    if bound.is_known() && bound != DataTypeType::Float {
+                        self.report(format!("Loop bounds must be floats, got {}", bound), span);
+                    }
+                }
+
+                self.variables.push(HashMap::new());
+                self.declare_variable(var_name, DataTypeType::Float, false);
+                self.visit(body, span);
+                self.variables.pop();
+                DataTypeType::Any
+            },
+            Expression::WhileLoop(condition, body) => {
+                self.visit(condition, span);
+                self.visit(body, span);
+                DataTypeType::Any
+            },
+            Expression::Break | Expression::Continue => DataTypeType::Any,
+        }
+    }
+
+    // mirrors the operators in `Expression::eval`
+    fn binary(&mut self, op: &str, lhs: &DataTypeType, rhs: &DataTypeType, span: Span) -> DataTypeType {
+        let known = lhs.is_known() && rhs.is_known();
+        let is = |ty: &DataTypeType, expected: DataTypeType| !ty.is_known() || *ty == expected;
+        let scalar = |ty: &DataTypeType| !ty.is_known() || matches!(ty, DataTypeType::Float | DataTypeType::Bool | DataTypeType::String);
+
+        let (valid, result) = match op {
+            "+" => match lhs {
+                DataTypeType::String => (true, DataTypeType::String),
+                DataTypeType::Float => (is(rhs, DataTypeType::Float), DataTypeType::Float),
+                _ => (!lhs.is_known(), DataTypeType::Any),
+            },
+            "-" | "*" | "/" | "~/" | "%" | "**" | "&" | "|" | "^" | "<<" | ">>" => {
+                (is(lhs, DataTypeType::Float) && is(rhs, DataTypeType::Float), DataTypeType::Float)
+            },
+            ".." => (scalar(lhs) && scalar(rhs), DataTypeType::String),
+            "&&" | "||" => (is(lhs, DataTypeType::Bool) && is(rhs, DataTypeType::Bool), DataTypeType::Bool),
+            "==" | "!=" => (true, DataTypeType::Bool),
+            "<" | ">" | "<=" | ">=" => {
+                let ordered = |ty: &DataTypeType| !ty.is_known() || matches!(ty, DataTypeType::Float | DataTypeType::String);
+                (ordered(lhs) && ordered(rhs) && (!known || lhs == rhs), DataTypeType::Bool)
+            },
+            _ => (true, DataTypeType::Any),
+        };
+
+        if !valid {
+            self.report(format!("Operator '{}' cannot be applied to {} and {}", op, lhs, rhs), span);
+        }
+        result
+    }
+
+    fn visit_assignment(&mut self, target: &Expression, value: &Expression, span: Span) -> DataTypeType {
+        let value_type = self.visit(value, span);
+        self.assign(target, &value_type, span);
+        value_type
+    }
+
+    fn assign(&mut self, target: &Expression, value_type: &DataTypeType, span: Span) {
+        match target {
+            Expression::Declaration(name, annotation) => {
+                let annotated = annotation.is_some();
+                let annotation = self.annotation(annotation, span);
+                if let Some(expected) = &annotation
+                    && !self.fits(expected, value_type)
+                {
+                    self.report(format!("Cannot assign {} to '{}' of type {}", value_type, name, expected), span);
+                }
+
+                let ty = match annotation {
+                    Some(annotation) => annotation,
+                    None if self.assigned_later.contains(name) => DataTypeType::Any,
+                    None => value_type.clone(),
+                };
+                self.declare_variable(name, ty, annotated);
+            },
+            Expression::Identifier(name) => {
+                let Some(var) = self.lookup(name) else {
+                    if !self.functions.is_empty() {
+                        self.assigned_later.insert(name.clone());
+                    }
+                    return;
+                };
+
+                let (expected, annotated) = (var.ty.clone(), var.annotated);
+                if annotated && !self.fits(&expected, value_type) {
+                    self.report(format!("Cannot assign {} to '{}' of type {}", value_type, name, expected), span);
+                } else if !annotated {
+                    let joined = expected.join(value_type);
+                    if let Some(var) = self.variables.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+                        var.ty = joined;
+                    }
+                }
+            },
+            Expression::Index(container, index) => {
+                let container = self.visit(container, span);
+                self.visit(index, span);
+                if let Some(element) = container.element()
+                    && !self.fits(&element, value_type)
+                {
+                    self.report(format!("Cannot store {} in {}", value_type, container), span);
+                }
+            },
+            Expression::Member(container, field) => {
+                let container = self.visit(container, span);
+                if let Some(expected) = self.field_type(&container, field, span)
+                    && !self.fits(&expected, value_type)
+                {
+                    self.report(format!("Field '{}' of {} is {}, got {}", field, container, expected, value_type), span);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // `x op= e` stores `x op e` back into `x`, the container of an indexed target is only visited once
+    fn visit_compound(&mut self, op: &str, target: &Expression, value: &Expression, span: Span) -> DataTypeType {
+        let current = self.visit(target, span);
+        let value = self.visit(value, span);
+        let result = self.binary(op, &current, &value, span);
+        if matches!(target, Expression::Identifier(_)) {
+            self.assign(target, &result, span);
+        } else if !self.fits(&current, &result) {
+            self.report(format!("Cannot assign {} to {} of type {}", result, target, current), span);
+        }
+
+        DataTypeType::Any
+    }
+
+    fn visit_call(&mut self, callee: &Expression, args: &[DataTypeType], span: Span) -> DataTypeType {
+        match callee {
+            // a native is only called when no variable or function of the same name is in scope, as at runtime
+            Expression::Identifier(name) if self.lookup(name).is_none() && self.natives.has(name) => DataTypeType::Any,
+            Expression::Member(target, name) => {
+                self.check_member(target, name, span);
+
+                if let Some(enum_name) = self.enum_named(target) {
+                    return self.enum_value(&enum_name, name, args);
+                }
+
+                // `Class::method()` while the class name is not shadowed by a variable
+                if let Expression::Identifier(class_name) = target.as_ref()
+                    && self.lookup(class_name).is_none()
+                    && let Some((_, method)) = self.find_method(class_name, name).filter(|(_, method)| method.is_static)
+                {
+                    let ty = self.resolve(&method.ty, &method.type_params);
+                    return self.call(&format!("{}::{}", class_name, name), &ty, args, span);
+                }
+
+                let receiver = self.visit(target, span);
+                let DataTypeType::Named(class_name, _) = &receiver else {
+                    return DataTypeType::Any;
+                };
+
+                if let Some((owner, method)) = self.find_method(class_name, name) {
+                    let (owner, visibility) = (owner.to_string(), method.visibility);
+                    let ty = substitute(&self.resolve(&method.ty, &method.type_params), &self.bindings(&receiver, &owner));
+                    if !self.allows(&owner, visibility) {
+                        self.report(format!("Method '{}::{}' is {}", owner, name, visibility), span);
+                    }
+                    return self.call(&format!("{}::{}", owner, name), &ty, args, span);
+                }
+
+                match self.field_type(&receiver, name, span) {
+                    Some(field) => self.call(name, &field, args, span),
+                    None => DataTypeType::Any,
+                }
+            },
+            _ => {
+                let ty = self.visit(callee, span);
+                // `f(x)` and `obj.f(x)` are named in messages, anything else only by its type
+                let name = match callee {
+                    Expression::Identifier(_) | Expression::Member(..) => callee.to_string(),
+                    _ => String::new(),
+                };
+                self.call(&name, &ty, args, span)
+            },
+        }
+    }
+
+    fn call(&mut self, name: &str, callee: &DataTypeType, args: &[DataTypeType], span: Span) -> DataTypeType {
+        match callee {
+            DataTypeType::Function(Some(params), returns) => {
+                if params.len() != args.len() {
+                    self.report(format!("Function '{}' expects {} arguments, got {}", name, params.len(), args.len()), span);
+                }
+
+                // type parameters are inferred from the arguments
+                let mut bindings = HashMap::new();
+                for (param, arg) in params.iter().zip(args) {
+                    unify(param, arg, &mut bindings);
+                }
+
+                for (idx, (param, arg)) in params.iter().zip(args).enumerate() {
+                    let param = substitute(param, &bindings);
+                    if !self.fits(&param, arg) {
+                        self.report(format!("Argument {} of '{}' expects {}, got {}", idx + 1, name, param, arg), span);
+                    }
+                }
+                erase(&substitute(returns, &bindings))
+            },
+            DataTypeType::Function(None, returns) => erase(returns),
+            DataTypeType::Any | DataTypeType::Param(_) => DataTypeType::Any,
+            // enum variants are built through their enum, which is `any` here
+            other => {
+                let message = if name.is_empty() {
+                    format!("Cannot call a value of type {}", other)
+                } else {
+                    format!("Cannot call '{}', it is a {}", name, other)
+                };
+                self.report(message, span);
+                DataTypeType::Any
+            },
+        }
+    }
+
+    // checks a function body in a scope of its own, returning the type of the function
+    fn visit_function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        body: &Expression,
+        signature: &Signature,
+        receiver: Option<DataTypeType>,
+        span: Span,
+    ) -> DataTypeType {
+        let outer_params = self.type_params.len();
+        self.type_params.extend(signature.type_params.iter().cloned());
+        let annotations: Vec<Option<DataTypeType>> = signature.params.iter().map(|param| self.annotation(param, span)).collect();
+        let returns = self.annotation(&signature.returns, span);
+
+        self.variables.push(HashMap::new());
+        if let Some(receiver) = receiver {
+            self.declare_variable("self", receiver, true);
+        }
+        for ((param, annotation), written) in params.iter().zip(&annotations).zip(&signature.params) {
+            self.declare_variable(param, annotation.clone().unwrap_or(DataTypeType::Any), written.is_some());
+        }
+
+        self.functions.push(FunctionContext { name: name.to_string(), returns: returns.clone(), returned: Vec::new() });
+        let value = self.visit(body, span);
+        // an arrow function without a block returns its expression
+        if !body.is_block() {
+            self.visit_return(value, span);
+        }
+        let context = self.functions.pop().expect("pushed above");
+        self.variables.pop();
+        self.type_params.truncate(outer_params);
+
+        let params = annotations.into_iter().map(|param| param.unwrap_or(DataTypeType::Any)).collect();
+        let returns = returns.unwrap_or_else(|| join_all(&context.returned));
+        DataTypeType::Function(Some(params), Box::new(returns))
+    }
+
+    // a written annotation with what is wrong in it reported, unknown names are `any` from here on
+    fn annotation(&mut self, annotation: &Option<DataTypeType>, span: Span) -> Option<DataTypeType> {
+        let annotation = annotation.as_ref()?;
+        let mut problems = Vec::new();
+        self.annotation_problems(annotation, &mut problems);
+        for problem in problems {
+            self.report(problem, span);
+        }
+
+        Some(self.resolve(annotation, &self.type_params))
+    }
+
+    fn annotation_problems(&self, ty: &DataTypeType, problems: &mut Vec<String>) {
+        match ty {
+            DataTypeType::Named(name, params) if params.is_empty() && self.type_params.contains(name) => {},
+            DataTypeType::Named(name, params) => {
+                match self.type_arity.get(name) {
+                    None => problems.push(format!("Unknown type '{}'", name)),
+                    Some(arity) if !params.is_empty() && params.len() != *arity => {
+                        problems.push(format!("Type '{}' takes {} type parameters, got {}", name, arity, params.len()));
+                    },
+                    _ if !self.well_formed(name, params) => problems.push(format!("Map keys are strings, got {}", params[0])),
+                    _ => {},
+                }
+                params.iter().for_each(|param| self.annotation_problems(param, problems));
+            },
+            DataTypeType::Function(params, returns) => {
+                params.iter().flatten().for_each(|param| self.annotation_problems(param, problems));
+                self.annotation_problems(returns, problems);
+            },
+            _ => {},
+        }
+    }
+
+    // the parameters of a known type, those left out are all `any`
+    fn well_formed(&self, name: &str, params: &[DataTypeType]) -> bool {
+        let arity_fits = params.is_empty() || self.type_arity.get(name) == Some(&params.len());
+        arity_fits && !(name == "Map" && params.first().is_some_and(|key| key.is_known() && *key != DataTypeType::String))
+    }
+
+    // names in `type_params` become type parameters, names that are not a type become `any`
+    fn resolve(&self, ty: &DataTypeType, type_params: &[String]) -> DataTypeType {
+        match ty {
+            DataTypeType::Named(name, params) if params.is_empty() && type_params.contains(name) => DataTypeType::Param(name.clone()),
+            DataTypeType::Named(name, params) if !self.type_arity.contains_key(name) || !self.well_formed(name, params) => DataTypeType::Any,
+            DataTypeType::Named(name, params) => {
+                DataTypeType::Named(name.clone(), params.iter().map(|param| self.resolve(param, type_params)).collect())
+            },
+            DataTypeType::Function(params, returns) => DataTypeType::Function(
+                params.as_ref().map(|params| params.iter().map(|param| self.resolve(param, type_params)).collect()),
+                Box::new(self.resolve(returns, type_params)),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    // the type arguments of `receiver` by the names `owner` declared them with
+    fn bindings(&self, receiver: &DataTypeType, owner: &str) -> HashMap<String, DataTypeType> {
+        match (receiver, self.classes.get(owner)) {
+            (DataTypeType::Named(name, args), Some(class)) if name == owner && class.type_params.len() == args.len() => {
+                class.type_params.iter().cloned().zip(args.iter().cloned()).collect()
+            },
+            _ => HashMap::new(),
+        }
+    }
+
+    // `Enum.Variant` and `Enum.Variant(...)` while the enum name is not shadowed by a variable
+    fn enum_named(&self, target: &Expression) -> Option<String> {
+        match target {
+            Expression::Identifier(name) if self.lookup(name).is_none() && self.enums.contains(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    // the payload of `Option` and `Result` is what their type parameters stand for
+    fn enum_value(&self, enum_name: &str, variant: &str, payload: &[DataTypeType]) -> DataTypeType {
+        let value = payload.first().cloned().unwrap_or(DataTypeType::Any);
+        let params = match (enum_name, variant) {
+            ("Option", "Some") => vec![value],
+            ("Option", _) => vec![DataTypeType::Any],
+            ("Result", "Ok") => vec![value, DataTypeType::Any],
+            ("Result", _) => vec![DataTypeType::Any, value],
+            _ => Vec::new(),
+        };
+        DataTypeType::Named(enum_name.to_string(), params)
+    }
+
+    fn visit_return(&mut self, value: DataTypeType, span: Span) {
+        let Some(context) = self.functions.last() else {
+            return;
+        };
+
+        if let Some(expected) = &context.returns
+            && !self.fits(expected, &value)
+        {
+            let message = format!("Function '{}' returns {}, got {}", context.name, expected, value);
+            self.report(message, span);
+        }
+        if let Some(context) = self.functions.last_mut() {
+            context.returned.push(value);
+        }
+    }
+
+    fn visit_member(&mut self, target: &Expression, name: &str, span: Span) -> DataTypeType {
+        self.check_member(target, name, span);
+        if self.resolve_module(target).is_some() {
+            return DataTypeType::Any;
+        }
+
+        if let Some(enum_name) = self.enum_named(target) {
+            return self.enum_value(&enum_name, name, &[]);
+        }
+
+        let receiver = self.visit(target, span);
+        if let Some(field) = self.field_type(&receiver, name, span) {
+            return field;
+        }
+        let DataTypeType::Named(class_name, _) = &receiver else {
+            return DataTypeType::Any;
+        };
+        match self.find_method(class_name, name) {
+            Some((owner, method)) => substitute(&self.resolve(&method.ty, &method.type_params), &self.bindings(&receiver, owner)),
+            None => DataTypeType::Any,
+        }
+    }
+
+    // the type of a field of `receiver`, reporting it when it cannot be reached from here
+    fn field_type(&mut self, receiver: &DataTypeType, field: &str, span: Span) -> Option<DataTypeType> {
+        let DataTypeType::Named(class_name, _) = receiver else {
+            return None;
+        };
+        let (owner, decl) = self.find_field(class_name, field)?;
+        let (owner, visibility) = (owner.to_string(), decl.visibility);
+        if !self.allows(&owner, visibility) {
+            self.report(format!("Field '{}' of '{}' is {}", field, owner, visibility), span);
+        }
+
+        let declared = self.declared_field_type(&owner, field).unwrap_or(DataTypeType::Any);
+        Some(substitute(&declared, &self.bindings(receiver, &owner)))
+    }
+
+    // the annotation of a field as written in `class_name` or its bases, generic in their type parameters
+    fn declared_field_type(&self, class_name: &str, field: &str) -> Option<DataTypeType> {
+        let (owner, decl) = self.find_field(class_name, field)?;
+        let type_params = self.classes.get(owner).map(|class| class.type_params.as_slice()).unwrap_or_default();
+        Some(decl.annotation.as_ref().map(|annotation| self.resolve(annotation, type_params)).unwrap_or(DataTypeType::Any))
+    }
+
+    fn check_member(&mut self, target: &Expression, name: &str, span: Span) {
+        if let Some((module_name, module)) = self.resolve_module(target) {
+            if module.members.iter().any(|member| member == name) && !module.exports.iter().any(|export| export == name) {
+                let message = format!("'{}' is private to module '{}'", name, module_name);
+                self.report(message, span);
+            }
+            return;
+        }
+
+        let Expression::Identifier(class_name) = target else {
+            return;
+        };
+        if let Some((owner, method)) = self.find_method(class_name, name)
+            && method.is_static
+            && !self.allows(owner, method.visibility)
+        {
+            let message = format!("Method '{}::{}' is {}", class_name, name, method.visibility);
+            self.report(message, span);
+        }
+    }
+
+    fn resolve_module(&self, expr: &Expression) -> Option<(&str, &ModuleShape)> {
+        match expr {
+            Expression::Identifier(name) if self.lookup(name).is_none() => {
+                self.modules.get_key_value(name).map(|(name, shape)| (name.as_str(), shape))
+            },
+            Expression::Member(target, name) => {
+                let (_, parent) = self.resolve_module(target)?;
+                parent.modules.get_key_value(name).map(|(name, shape)| (name.as_str(), shape))
+            },
+            _ => None,
+        }
+    }
+
+    // `class_name` and its bases, most derived first. bounded, a base chain with a cycle must not hang the checker
+    fn lineage(&self, class_name: &str) -> Vec<(&str, &ClassShape)> {
+        let mut lineage = Vec::new();
+        let mut current = self.classes.get_key_value(class_name);
+        while let Some((name, class)) = current {
+            if lineage.len() > self.classes.len() {
+                break;
+            }
+            lineage.push((name.as_str(), class));
+            current = class.base.as_ref().and_then(|base| self.classes.get_key_value(base));
+        }
+        lineage
+    }
+
+    fn find_field(&self, class_name: &str, field: &str) -> Option<(&str, &FieldDecl)> {
+        self.lineage(class_name)
+            .into_iter()
+            .find_map(|(owner, class)| class.fields.iter().find(|decl| decl.name == field).map(|decl| (owner, decl)))
+    }
+
+    fn find_method(&self, class_name: &str, method: &str) -> Option<(&str, &MethodShape)> {
+        self.lineage(class_name)
+            .into_iter()
+            .find_map(|(owner, class)| class.methods.iter().find(|shape| shape.name == method).map(|shape| (owner, shape)))
+    }
+
+    // same rules as ClassDef::allows, by name since nothing is instantiated yet
+    fn allows(&self, owner: &str, visibility: Visibility) -> bool {
+        let Some(context) = &self.class_context else {
+            return visibility == Visibility::Public;
+        };
+
+        match visibility {
+            Visibility::Public => true,
+            Visibility::Private => context == owner,
+            Visibility::Protected => self.lineage(context).iter().any(|(name, _)| *name == owner),
+        }
+    }
+
+    // whether a value of type `actual` can go where `expected` is wanted, instances of a subclass fit their bases
+    fn fits(&self, expected: &DataTypeType, actual: &DataTypeType) -> bool {
+        match (expected, actual) {
+            (DataTypeType::Any | DataTypeType::Param(_), _) | (_, DataTypeType::Any | DataTypeType::Param(_)) => true,
+            (DataTypeType::Function(expected_params, expected_returns), DataTypeType::Function(actual_params, actual_returns)) => {
+                let arity_matches = match (expected_params, actual_params) {
+                    (Some(expected), Some(actual)) => expected.len() == actual.len(),
+                    _ => true,
+                };
+                arity_matches && self.fits(expected_returns, actual_returns)
+            },
+            (DataTypeType::Named(expected, expected_params), DataTypeType::Named(actual, actual_params)) => {
+                let params_fit = expected_params.is_empty()
+                    || actual_params.is_empty()
+                    || (expected_params.len() == actual_params.len()
+                        && expected_params.iter().zip(actual_params).all(|(expected, actual)| self.fits(expected, actual)));
+                params_fit && (expected == actual || self.lineage(actual).iter().any(|(name, _)| name == expected))
+            },
+            _ => expected == actual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::language::tokens::Program;
+
+    // the checker's messages with the line each one points at
+    fn check(source: &str) -> Vec<(usize, String)> {
+        let mut program = Program::new();
+        program.tokenize(source).unwrap();
+
+        match program.check() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|err| (err.span.map_or(0, |span| span.line), err.message)).collect(),
+        }
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        check(source).into_iter().map(|(_, message)| message).collect()
+    }
+
+    #[test]
+    fn infers_declarations_and_operators() {
+        assert_eq!(messages("let count = 3;\nlet name = \"n\";\nlet total: float = count + 1;\nlet label: string = name .. count;"), Vec::<String>::new());
+        assert_eq!(messages("let x = \"a\" - 1;"), ["Operator '-' cannot be applied to string and float"]);
+        assert_eq!(messages("let count = 3;\nlet name: string = count;"), ["Cannot assign float to 'name' of type string"]);
+    }
+
+    #[test]
+    fn infers_function_signatures() {
+        let source = "
+function area(w: float, h: float) -> float { return w * h; }
+let a: float = area(2, 3);
+let b: string = area(2, 3);
+area(\"2\", 3);
+";
+        assert_eq!(messages(source), [
+            "Cannot assign float to 'b' of type string",
+            "Argument 1 of 'area' expects float, got string",
+        ]);
+    }
+
+    #[test]
+    fn unannotated_code_is_not_rejected() {
+        assert_eq!(messages("function greet(who) { return \"hi \" .. who; }\nprint(greet(1));"), Vec::<String>::new());
+        // a function may give the variable another type before it is used
+        assert_eq!(messages("function reset() { v = 0 }\nlet v = \"s\";\nreset();\nprint(v - 1)"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn accepts_function_annotations() {
+        assert_eq!(messages("let twice: function = (n: float) -> float => n * 2;\nprint(twice(2));"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn diagnostics_point_at_their_own_statement() {
+        let source = "function f() {\n    let ok = 1;\n    let bad = \"a\" - ok;\n}\nlet fine = 2;\nlet worse: string = fine;";
+        let lines: Vec<usize> = check(source).into_iter().map(|(line, _)| line).collect();
+        assert_eq!(lines, [3, 6]);
+    }
+
+    #[test]
+    fn instantiates_generic_functions_and_classes() {
+        let source = "
+class Box<T> {
+  public value: T,
+  public method get() -> T { return self.value; }
+}
+function first<T>(xs: [T]) -> T { return xs[0]; }
+let c = Box { value: \"hi\" };
+let s: string = c.get();
+let f: float = first([1, 2, 3]);
+let x: float = c.get();
+let y: float = first([\"a\"]);
+";
+        assert_eq!(messages(source), [
+            "Cannot assign string to 'x' of type float",
+            "Cannot assign string to 'y' of type float",
+        ]);
+    }
+
+    #[test]
+    fn type_parameters_must_agree() {
+        assert_eq!(messages("function pair<T>(a: T, b: T) -> [T] { return [a, b]; }\nlet z = pair(1, \"a\");"), ["Argument 2 of 'pair' expects float, got string"]);
+    }
+
+    #[test]
+    fn checks_builtin_generics() {
+        let source = "
+let w: Option<float> = Option.Some(\"s\");
+let e: Result<float, string> = Result.Err(3);
+let k: Map<float, float> = {};
+let arr: [float] = [1];
+arr[0] = \"s\";
+let ok: Result<float, string> = Result.Ok(1);
+";
+        assert_eq!(messages(source), [
+            "Cannot assign Option<string> to 'w' of type Option<float>",
+            "Cannot assign Result<any, float> to 'e' of type Result<float, string>",
+            "Map keys are strings, got float",
+            "Cannot store string in [float]",
+        ]);
+    }
+
+    #[test]
+    fn checks_type_parameter_counts() {
+        assert_eq!(messages("class Box<T> { public value: T }\nlet w: Box<float, string> = Box { value: 1 };"), ["Type 'Box' takes 1 type parameters, got 2"]);
+        assert_eq!(messages("let z: [Pointt] = [];"), ["Unknown type 'Pointt'"]);
+    }
+}
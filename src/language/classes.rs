@@ -0,0 +1,120 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::language::{datatypes::DataType, errors::LangError, expressions::{MethodDecl, Visibility}, scopes::Environment};
+
+#[derive(Debug, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub base: Option<Rc<ClassDef>>,
+    pub fields: Vec<(String, Visibility)>,
+    pub methods: Vec<MethodDecl>,
+    pub env: Environment, // scopes the class was declared in, methods run on top of them
+}
+
+#[derive(Debug)]
+pub struct Object {
+    pub class: Rc<ClassDef>,
+    pub fields: Vec<(String, DataType)>,
+}
+
+// instances are shared, copying one copies the reference
+#[derive(Clone)]
+pub struct ObjectRef(pub Rc<RefCell<Object>>);
+
+impl PartialEq for ObjectRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// objects can reach themselves through their fields, so only the class is printed
+impl std::fmt::Debug for ObjectRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Object<{}>", self.0.borrow().class.name)
+    }
+}
+
+impl ClassDef {
+    // base classes first, so a derived instance lists inherited fields before its own
+    pub fn all_fields(&self) -> Vec<String> {
+        let mut fields = self.base.as_ref().map(|base| base.all_fields()).unwrap_or_default();
+        fields.extend(self.fields.iter().map(|(name, _)| name.clone()));
+        fields
+    }
+
+    // the class along the base chain that declares method `name` and its index there, the most derived one wins
+    pub fn method_owner(class: &Rc<ClassDef>, name: &str) -> Option<(Rc<ClassDef>, usize)> {
+        match class.methods.iter().position(|method| method.name == name) {
+            Some(idx) => Some((class.clone(), idx)),
+            None => class.base.as_ref().and_then(|base| ClassDef::method_owner(base, name)),
+        }
+    }
+
+    pub fn field_owner(&self, name: &str) -> Option<(&ClassDef, Visibility)> {
+        match self.fields.iter().find(|(field, _)| field == name) {
+            Some((_, visibility)) => Some((self, *visibility)),
+            None => self.base.as_ref().and_then(|base| base.field_owner(name)),
+        }
+    }
+
+    pub fn derives_from(&self, other: &ClassDef) -> bool {
+        std::ptr::eq(self, other) || self.base.as_ref().is_some_and(|base| base.derives_from(other))
+    }
+
+    // whether code running in a method of `context` may use a member that `self` declares
+    pub fn allows(&self, visibility: Visibility, context: Option<&ClassDef>) -> bool {
+        match visibility {
+            Visibility::Public => true,
+            Visibility::Private => context.is_some_and(|context| std::ptr::eq(context, self)),
+            Visibility::Protected => context.is_some_and(|context| context.derives_from(self)),
+        }
+    }
+
+    pub fn check_field_access(&self, name: &str, context: Option<&ClassDef>) -> Result<(), LangError> {
+        match self.field_owner(name) {
+            Some((owner, visibility)) if !owner.allows(visibility, context) => {
+                Err(LangError::new(format!("Field '{}' of '{}' is {}", name, owner.name, visibility)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // a literal may set every field, visibility applies to objects once they exist
+    pub fn instantiate(class: &Rc<ClassDef>, mut values: Vec<(String, DataType)>) -> Result<DataType, LangError> {
+        let fields = class.all_fields();
+        if let Some((unknown, _)) = values.iter().find(|(name, _)| !fields.contains(name)) {
+            return Err(LangError::new(format!("Class '{}' has no field '{}'", class.name, unknown)));
+        }
+
+        let mut ordered = Vec::new();
+        for field in fields {
+            let Some(idx) = values.iter().position(|(name, _)| *name == field) else {
+                return Err(LangError::new(format!("Missing field '{}' in '{}' literal", field, class.name)));
+            };
+            ordered.push(values.remove(idx));
+        }
+
+        let object = Object { class: class.clone(), fields: ordered };
+        Ok(DataType::Object(ObjectRef(Rc::new(RefCell::new(object)))))
+    }
+}
+
+impl Object {
+    pub fn get_field(&self, name: &str) -> Result<DataType, LangError> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| LangError::new(format!("Class '{}' has no field '{}'", self.class.name, name)))
+    }
+
+    pub fn set_field(&mut self, name: &str, value: DataType) -> Result<(), LangError> {
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, existing)) => {
+                *existing = value;
+                Ok(())
+            },
+            None => Err(LangError::new(format!("Class '{}' has no field '{}'", self.class.name, name))),
+        }
+    }
+}
@@ -1,24 +1,133 @@
+use std::rc::Rc;
+
+use crate::language::classes::{ClassDef, ObjectRef};
+use crate::language::errors::LangError;
 use crate::language::expressions::Expression;
+use crate::language::scopes::{Environment, Module};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum DataType {
     Float(f32),
     Bool(bool),
     String(String),
-    Function(Vec<String>, Expression),
+    Function(Vec<String>, Expression, Environment), // (params, body, captured scopes)
     Return(Box<DataType>),
     Array(Vec<DataType>),
+    Map(Vec<(String, DataType)>), // entries in insertion order
+    Enum(Rc<EnumDef>), // the enum itself, `Shape` in `Shape.Circle(1)`
+    EnumValue(String, String, Vec<(String, DataType)>), // (enum, variant, payload fields)
+    Class(Rc<ClassDef>),
+    Object(ObjectRef),
+    Module(Rc<Module>),
+    NativeFunction(String), // a function from the `FunctionRegistry`, by name
     Continue,
     Break,
     EndOfBlock,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<(String, Vec<String>)>, // (variant, payload field names)
+}
+
+impl EnumDef {
+    pub fn construct(&self, variant: &str, payload: Vec<DataType>) -> Result<DataType, LangError> {
+        let Some((_, fields)) = self.variants.iter().find(|(name, _)| name == variant) else {
+            return Err(LangError::new(format!("Enum '{}' has no variant '{}'", self.name, variant)));
+        };
+
+        if fields.len() != payload.len() {
+            return Err(LangError::new(format!(
+                "Variant '{}.{}' expects {} values, got {}",
+                self.name, variant, fields.len(), payload.len()
+            )));
+        }
+
+        let payload = fields.iter().cloned().zip(payload).collect();
+        Ok(DataType::EnumValue(self.name.clone(), variant.to_string(), payload))
+    }
+}
+
+// the type of a value as written in annotations and inferred by the checker
+#[derive(Clone, Debug, PartialEq)]
 pub enum DataTypeType {
     Float,
     Bool,
     String,
-    Function,
+    Function(Option<Vec<DataTypeType>>, Box<DataTypeType>), // (params, returns), params are None when the arity is unknown
+    // a class, an enum or a built-in container, `Name<A, B>`. arrays are `Array<T>` and written `[T]`
+    Named(String, Vec<DataTypeType>),
+    Param(String), // `T` inside a generic class or function, bound at each use
+    Any, // unannotated and not inferred, fits everywhere
+}
+
+// built-in generic types and how many parameters they take
+pub const BUILTIN_GENERICS: [(&str, usize); 4] = [("Array", 1), ("Map", 2), ("Option", 1), ("Result", 2)];
+
+impl DataTypeType {
+    // `float`, `function` or the name of a class, enum or built-in generic with its type parameters
+    pub fn from_annotation(name: &str, params: Vec<DataTypeType>) -> Self {
+        match (name, params.is_empty()) {
+            ("float", true) => DataTypeType::Float,
+            ("bool", true) => DataTypeType::Bool,
+            ("string", true) => DataTypeType::String,
+            ("any", true) => DataTypeType::Any,
+            ("function", true) => DataTypeType::Function(None, Box::new(DataTypeType::Any)),
+            _ => DataTypeType::Named(name.to_string(), params),
+        }
+    }
+
+    pub fn array(element: DataTypeType) -> Self {
+        DataTypeType::Named("Array".to_string(), vec![element])
+    }
+
+    // maps are keyed by strings
+    pub fn map(value: DataTypeType) -> Self {
+        DataTypeType::Named("Map".to_string(), vec![DataTypeType::String, value])
+    }
+
+    // the type two values share, anything but an exact match is only known at runtime
+    pub fn join(&self, other: &DataTypeType) -> DataTypeType {
+        if self == other { self.clone() } else { DataTypeType::Any }
+    }
+
+    // what indexing an array or a map gives
+    pub fn element(&self) -> Option<DataTypeType> {
+        match self {
+            DataTypeType::Named(name, params) if name == "Array" && params.len() == 1 => Some(params[0].clone()),
+            DataTypeType::Named(name, params) if name == "Map" && params.len() == 2 => Some(params[1].clone()),
+            _ => None,
+        }
+    }
+
+    // type parameters stand for any type until they are bound
+    pub fn is_known(&self) -> bool {
+        !matches!(self, DataTypeType::Any | DataTypeType::Param(_))
+    }
+}
+
+impl std::fmt::Display for DataTypeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataTypeType::Float => write!(f, "float"),
+            DataTypeType::Bool => write!(f, "bool"),
+            DataTypeType::String => write!(f, "string"),
+            DataTypeType::Function(None, _) => write!(f, "function"),
+            DataTypeType::Function(Some(params), returns) => {
+                let params = params.iter().map(|param| param.to_string()).collect::<Vec<_>>().join(", ");
+                write!(f, "function({}) -> {}", params, returns)
+            },
+            DataTypeType::Named(name, params) if name == "Array" && params.len() == 1 => write!(f, "[{}]", params[0]),
+            DataTypeType::Named(name, params) if params.is_empty() => write!(f, "{}", name),
+            DataTypeType::Named(name, params) => {
+                let params = params.iter().map(|param| param.to_string()).collect::<Vec<_>>().join(", ");
+                write!(f, "{}<{}>", name, params)
+            },
+            DataTypeType::Param(name) => write!(f, "{}", name),
+            DataTypeType::Any => write!(f, "any"),
+        }
+    }
 }
 
 impl DataType {
@@ -52,28 +161,196 @@ impl DataType {
             DataType::Float(_) => DataTypeType::Float,
             DataType::Bool(_) => DataTypeType::Bool,
             DataType::String(_) => DataTypeType::String,
-            DataType::Function(..) => DataTypeType::Function,
+            DataType::Function(params, ..) => DataTypeType::Function(Some(vec![DataTypeType::Any; params.len()]), Box::new(DataTypeType::Any)),
+            DataType::NativeFunction(_) => DataTypeType::Function(None, Box::new(DataTypeType::Any)),
+            DataType::Array(_) => DataTypeType::array(DataTypeType::Any),
+            DataType::Map(_) => DataTypeType::map(DataTypeType::Any),
+            DataType::EnumValue(name, ..) => DataTypeType::Named(name.clone(), Vec::new()),
+            DataType::Object(object) => DataTypeType::Named(object.0.borrow().class.name.clone(), Vec::new()),
             DataType::Return(inner) => inner.get_type(),  // Get inner type
-            _ => panic!("Cannot get type of eof"),
+            _ => DataTypeType::Any,
         }
     }
     
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataType::Float(_) => "float",
+            DataType::Bool(_) => "bool",
+            DataType::String(_) => "string",
+            DataType::Function(..) => "function",
+            DataType::Array(_) => "array",
+            DataType::Map(_) => "map",
+            DataType::Enum(_) => "enum",
+            DataType::EnumValue(..) => "enum value",
+            DataType::Class(_) => "class",
+            DataType::Object(_) => "object",
+            DataType::Module(_) => "module",
+            DataType::NativeFunction(_) => "function",
+            DataType::Return(inner) => inner.type_name(),
+            DataType::Continue | DataType::Break | DataType::EndOfBlock => "nothing",
+        }
+    }
+
+    // element `index` of an array or the character at `index` of a string, negative indices count from the end
+    pub fn get_index(&self, index: &DataType) -> Result<DataType, LangError> {
+        match self {
+            DataType::Array(elements) => {
+                let idx = resolve_index(index, elements.len())?;
+                Ok(elements[idx].clone())
+            },
+            DataType::String(str) => {
+                let chars: Vec<char> = str.chars().collect();
+                let idx = resolve_index(index, chars.len())?;
+                Ok(DataType::String(chars[idx].to_string()))
+            },
+            DataType::Map(entries) => {
+                let key = map_key(index)?;
+                entries
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, value)| value.clone())
+                    .ok_or_else(|| LangError::new(format!("Key {:?} not found in map", key)))
+            },
+            other => Err(LangError::new(format!("Cannot index into {}", other.type_name()))),
+        }
+    }
+
+    pub fn set_index(&mut self, index: &DataType, value: DataType) -> Result<(), LangError> {
+        match self {
+            DataType::Array(elements) => {
+                let idx = resolve_index(index, elements.len())?;
+                elements[idx] = value;
+                Ok(())
+            },
+            DataType::String(_) => Err(LangError::new("Strings are immutable, cannot assign to an index".to_string())),
+            // a new key is appended, an existing key keeps its position
+            DataType::Map(entries) => {
+                let key = map_key(index)?;
+                match entries.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, existing)) => *existing = value,
+                    None => entries.push((key, value)),
+                }
+                Ok(())
+            },
+            other => Err(LangError::new(format!("Cannot index into {}", other.type_name()))),
+        }
+    }
+
+    // `Enum.Variant` for unit variants and `value.field` for payload fields
+    pub fn get_member(&self, name: &str) -> Result<DataType, LangError> {
+        match self {
+            DataType::Enum(def) => def.construct(name, Vec::new()),
+            DataType::EnumValue(enum_name, variant, payload) => payload
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| LangError::new(format!("Variant '{}.{}' has no field '{}'", enum_name, variant, name))),
+            DataType::Object(object) => object.0.borrow().get_field(name),
+            DataType::Module(module) => module.get(name),
+            other => Err(LangError::new(format!("{} has no member '{}'", other.type_name(), name))),
+        }
+    }
+
+    // half-open `start..end`, a missing bound means the start or end of the value
+    pub fn slice(&self, start: Option<&DataType>, end: Option<&DataType>) -> Result<DataType, LangError> {
+        let len = match self {
+            DataType::Array(elements) => elements.len(),
+            DataType::String(str) => str.chars().count(),
+            other => return Err(LangError::new(format!("Cannot slice {}", other.type_name()))),
+        };
+
+        let start = start.map(|s| resolve_slice_bound(s, len)).transpose()?.unwrap_or(0);
+        let end = end.map(|e| resolve_slice_bound(e, len)).transpose()?.unwrap_or(len);
+        let end = end.max(start);
+
+        match self {
+            DataType::Array(elements) => Ok(DataType::Array(elements[start..end].to_vec())),
+            DataType::String(str) => Ok(DataType::String(str.chars().skip(start).take(end - start).collect())),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn as_string(&self) -> String {
         match self {
             DataType::String(str) => format!("{}", str),
             DataType::Float(val) => format!("{}", val),
             DataType::Bool(b) => format!("{}", b),
             DataType::Return(inner) => inner.as_string(),  // Unwrap for conversions
-            DataType::Array(elements) => format!("[{}]", elements.iter().map(|e| e.as_string()).collect::<Vec<String>>().join(", ")),
-            _ => panic!("Cannot evaluate variable as string"),
+            DataType::Array(elements) => format!("[{}]", elements.iter().map(|e| e.as_element_string()).collect::<Vec<String>>().join(", ")),
+            DataType::Map(entries) => format!("{{{}}}", entries.iter().map(|(k, v)| format!("{:?}: {}", k, v.as_element_string())).collect::<Vec<String>>().join(", ")),
+            DataType::Function(params, ..) => format!("<function({})>", params.join(", ")),
+            DataType::Enum(def) => format!("<enum {}>", def.name),
+            DataType::Class(class) => format!("<class {}>", class.name),
+            DataType::Module(module) => format!("<module {}>", module.name),
+            DataType::NativeFunction(name) => format!("<native {}>", name),
+            DataType::Object(object) => {
+                let object = object.0.borrow();
+                let fields = object.fields.iter().map(|(k, v)| format!("{}: {}", k, v.as_element_string())).collect::<Vec<String>>();
+                format!("{} {{ {} }}", object.class.name, fields.join(", "))
+            },
+            DataType::EnumValue(enum_name, variant, payload) if payload.is_empty() => format!("{}.{}", enum_name, variant),
+            DataType::EnumValue(enum_name, variant, payload) => {
+                format!("{}.{}({})", enum_name, variant, payload.iter().map(|(_, v)| v.as_element_string()).collect::<Vec<String>>().join(", "))
+            },
+            // a block that produced no value prints as nothing, the same as `Display`
+            DataType::Continue | DataType::Break | DataType::EndOfBlock => String::new(),
         }
     }
     
-    pub fn is_truthy(&self) -> bool {
-        self.as_bool()
+    // strings are quoted inside containers so that `["a, b"]` and `["a", "b"]` print differently
+    fn as_element_string(&self) -> String {
+        match self {
+            DataType::String(str) => format!("{:?}", str),
+            other => other.as_string(),
+        }
+    }
+
+    // conditions take bools, and numbers and strings the way `as_bool` reads them
+    pub fn is_truthy(&self) -> Result<bool, LangError> {
+        match self {
+            DataType::Bool(_) | DataType::Float(_) | DataType::String(_) => Ok(self.as_bool()),
+            DataType::Return(inner) => inner.is_truthy(),
+            other => Err(LangError::new(format!("Condition must be a bool, got {}", other.type_name()))),
+        }
     }
 }
 
+pub fn map_key(key: &DataType) -> Result<String, LangError> {
+    match key {
+        DataType::String(key) => Ok(key.clone()),
+        other => Err(LangError::new(format!("Map keys must be strings, got {}", other.type_name()))),
+    }
+}
+
+fn integer_index(index: &DataType) -> Result<i64, LangError> {
+    match index {
+        DataType::Float(num) if num.fract() == 0.0 => Ok(*num as i64),
+        DataType::Float(num) => Err(LangError::new(format!("Index must be an integer, got {}", num))),
+        other => Err(LangError::new(format!("Index must be an integer, got {}", other.type_name()))),
+    }
+}
+
+fn resolve_index(index: &DataType, len: usize) -> Result<usize, LangError> {
+    let raw = integer_index(index)?;
+    let resolved = if raw < 0 { raw + len as i64 } else { raw };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(LangError::new(format!("Index {} out of bounds for length {}", raw, len)));
+    }
+
+    Ok(resolved as usize)
+}
+
+// like an index, negative bounds count from the end, but `len` itself is a valid bound
+fn resolve_slice_bound(bound: &DataType, len: usize) -> Result<usize, LangError> {
+    let raw = integer_index(bound)?;
+    let resolved = if raw < 0 { raw + len as i64 } else { raw };
+    if resolved < 0 || resolved > len as i64 {
+        return Err(LangError::new(format!("Slice bound {} out of bounds for length {}", raw, len)));
+    }
+
+    Ok(resolved as usize)
+}
+
 impl std::fmt::Display for DataType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -81,6 +358,10 @@ impl std::fmt::Display for DataType {
             DataType::Bool(b) => write!(f, "{}", b),
             DataType::String(str) => write!(f, "{}", str),
             DataType::Return(inner) => write!(f, "{}", inner),  // Display inner value
+            DataType::Array(_) | DataType::Map(_) | DataType::Function(..) | DataType::Enum(_) | DataType::EnumValue(..)
+            | DataType::Class(_) | DataType::Object(_) | DataType::Module(_) | DataType::NativeFunction(_) => {
+                write!(f, "{}", self.as_string())
+            },
             _ => Ok(()),
         }
     }
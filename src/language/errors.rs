@@ -1,43 +1,92 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::language::span::Span;
 
 #[derive(Debug, Clone)]
 pub struct LangError {
     pub message: String,
+    pub span: Option<Span>,
+    pub file: Option<PathBuf>, // set when the span is in an imported file
 }
 
 #[derive(Debug, Clone)]
 pub struct ProgramError {
     pub message: String,
     pub line_number: usize,
+    pub column: usize,
+    pub length: usize,
     pub line_text: String,
+    pub file: Option<String>, // set when the error is in an imported file rather than the one being run
 }
 
 impl ProgramError {
-    pub fn new(message: String, line_number: usize, line_text: String) -> Self {
+    pub fn new(message: String, span: Span, line_text: String) -> Self {
         ProgramError {
             message,
-            line_number,
+            line_number: span.line,
+            column: span.column,
+            length: span.length,
             line_text,
+            file: None,
         }
     }
+
+    pub fn in_file(self, file: &Path) -> Self {
+        ProgramError { file: Some(file.display().to_string()), ..self }
+    }
 }
 
 impl LangError {
     pub fn new(message: String) -> Self {
-        LangError { message }
+        LangError { message, span: None, file: None }
+    }
+
+    pub fn at(message: String, span: Span) -> Self {
+        LangError { message, span: Some(span), file: None }
+    }
+
+    pub fn or_at(self, span: Span) -> Self {
+        LangError { span: self.span.or(Some(span)), ..self }
+    }
+
+    // the innermost file wins, an error passed up through several imports keeps the file it happened in
+    pub fn in_file(self, file: &Path) -> Self {
+        LangError { file: self.file.or_else(|| Some(file.to_path_buf())), ..self }
     }
 }
 
 impl fmt::Display for LangError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\x1b[1;31m[Error]:\x1b[0m {}", self.message)
+        match self.span {
+            Some(span) => write!(f, "\x1b[1;31m[Error]:\x1b[0m {} (at {})", self.message, span),
+            None => write!(f, "\x1b[1;31m[Error]:\x1b[0m {}", self.message),
+        }
     }
 }
 
 impl fmt::Display for ProgramError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\x1b[1;31m[Error]:\x1b[0m {}\n| On line \x1b[1;33m[{}]:\x1b[0m \"\x1b[1;35m{}\x1b[0m\"", self.message, self.line_number, self.line_text)
+        let file = self.file.as_ref().map(|file| format!("In {} on line", file)).unwrap_or_else(|| "On line".to_string());
+        let location = format!("| {} [{}:{}]: \"", file, self.line_number, self.column);
+        write!(f, "\x1b[1;31m[Error]:\x1b[0m {}\n| {} \x1b[1;33m[{}:{}]:\x1b[0m \"\x1b[1;35m{}\x1b[0m\"", self.message, file, self.line_number, self.column, self.line_text)?;
+
+        // marker under the offending token, tabs are kept so it lines up with the quoted source
+        let padding: String = self.line_text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let start = self.line_text
+            .char_indices()
+            .nth(self.column.saturating_sub(1))
+            .map(|(idx, _)| idx)
+            .unwrap_or(self.line_text.len());
+        let end = (start + self.length).min(self.line_text.len());
+        let marker_len = self.line_text.get(start..end).map(|s| s.chars().count()).unwrap_or(0).max(1);
+
+        write!(f, "\n|{}{}\x1b[1;31m{}\x1b[0m", " ".repeat(location.len() - 1), padding, "^".repeat(marker_len))
     }
 }
 
-impl std::error::Error for LangError {}
\ No newline at end of file
+impl std::error::Error for LangError {}
@@ -1,20 +1,87 @@
-use crate::language::{datatypes::{DataType, DataTypeType}, errors::LangError, scopes::ScopeStack};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::language::{classes::{ClassDef, ObjectRef}, datatypes::{DataType, DataTypeType, EnumDef}, errors::LangError, scopes::{Environment, Module, ScopeStack}, span::Span};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Number(f32),
+    Bool(bool),
+    String(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDecl {
+    pub name: String,
+    pub annotation: Option<DataTypeType>,
+    pub visibility: Visibility,
+}
+
+// the annotated types of a function, one entry per parameter, None where nothing was written
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Signature {
+    pub type_params: Vec<String>, // `<T>` of a generic function
+    pub params: Vec<Option<DataTypeType>>,
+    pub returns: Option<DataTypeType>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodDecl {
+    pub name: String,
+    pub params: Vec<String>,
+    pub signature: Signature,
+    pub body: Expression,
+    pub is_static: bool,
+    pub visibility: Visibility,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassDecl {
+    pub name: String,
+    pub type_params: Vec<String>,
+    pub base: Option<String>,
+    pub fields: Vec<FieldDecl>,
+    pub methods: Vec<MethodDecl>,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
-    Atom(String),
+    Literal(Literal),
+    Identifier(String),
     Operation(String, Vec<Expression>),
-    Declaration(String),
+    Unary(String, Box<Expression>),
+    Declaration(String, Option<DataTypeType>), // `let name` or `let name: type`
     Array(Vec<Expression>),
+    Map(Vec<(String, Expression)>),
+    Interpolation(Vec<Expression>), // "text {expr} text", evaluated segment by segment
+    Index(Box<Expression>, Box<Expression>), // (target, index)
+    Slice(Box<Expression>, Option<Box<Expression>>, Option<Box<Expression>>), // (target, start, end)
+    CompoundAssignment(String, Box<Expression>, Box<Expression>), // (arithmetic op, target, value), `x += e` and `x++`
 
     // fn
-    FunctionCall(String, Box<Vec<Expression>>),
-    FunctionDeclaration(String, Vec<String>, Box<Expression>),
+    FunctionCall(Box<Expression>, Vec<Expression>), // (callee, args)
+    FunctionDeclaration(String, Vec<String>, Box<Expression>, Signature),
+    Closure(Vec<String>, Box<Expression>, Signature), // anonymous `function(x) ... end` or `(x) => ...`
+    EnumDeclaration(String, Vec<(String, Vec<String>)>), // (name, [(variant, payload fields)])
+    Member(Box<Expression>, String), // target.name or Target::name
+    ClassDeclaration(ClassDecl),
+    StructLiteral(String, Vec<(String, Expression)>), // Name { field: value }
+    ModuleDeclaration(String, Vec<(Expression, Span)>),
+    Import(String, PathBuf, Rc<Vec<(Expression, Span)>>), // (module name, resolved file, parsed file)
+    Using(Box<Expression>), // using Module;
+    Modified(Visibility, Box<Expression>), // public/private/protected in front of a declaration
     Return(Box<Expression>),
     
     // conditionals
     If(Box<Expression>, Box<Expression>, Vec<(Expression, Expression)>, Option<Box<Expression>>), 
-    Block(Vec<Expression>),
+    Block(Vec<(Expression, Span)>), // statements with where they were written
     ForLoop(String, Box<Expression>, Box<Expression>, Option<Box<Expression>>, Box<Expression>),
     WhileLoop(Box<Expression>, Box<Expression>),  // (condition, body)
     InfiniteLoop(Box<Expression>),  // (body)
@@ -22,10 +89,20 @@ pub enum Expression {
     Continue,  // continue statement
 }
 
+impl std::fmt::Display for Visibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Visibility::Public => write!(f, "public"),
+            Visibility::Private => write!(f, "private"),
+            Visibility::Protected => write!(f, "protected"),
+        }
+    }
+}
+
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expression::FunctionDeclaration(name, params, body) => {
+            Expression::FunctionDeclaration(name, params, body, _) => {
                 let mut param_str = String::new();
                 for param in params {
                     param_str.push_str(&format!("{}, ", param));
@@ -33,12 +110,31 @@ impl std::fmt::Display for Expression {
 
                 write!(f, "fn<{}({})> {{ {} }}", name, param_str, body)
             },
-            Expression::FunctionCall(fn_name, _) => {
+            Expression::FunctionCall(callee, _) => {
 
-                write!(f, "fn_call<{}>", fn_name)
+                write!(f, "fn_call<{}>", callee)
+            },
+            Expression::Declaration(val, _) => write!(f, "decl<{}>", val),
+            Expression::Literal(Literal::Number(val)) => write!(f, "{}", val),
+            Expression::Literal(Literal::Bool(val)) => write!(f, "{}", val),
+            Expression::Literal(Literal::String(val)) => write!(f, "{:?}", val),
+            Expression::Identifier(name) => write!(f, "{}", name),
+            Expression::Unary(op, operand) => write!(f, "({} {})", op, operand),
+            Expression::Index(target, index) => write!(f, "{}[{}]", target, index),
+            Expression::CompoundAssignment(op, target, value) => write!(f, "({}= {} {})", op, target, value),
+            Expression::Member(target, name) => write!(f, "{}.{}", target, name),
+            Expression::Modified(visibility, declaration) => write!(f, "{} {}", visibility, declaration),
+            Expression::Slice(target, start, end) => {
+                write!(f, "{}[", target)?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, "..")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "]")
             },
-            Expression::Declaration(val) => write!(f, "decl<{}>", val),
-            Expression::Atom(val) => write!(f, "{}", val),
             Expression::Operation(op, tree) => {
                 write!(f, "({}", op)?;
                 for expr in tree {
@@ -61,7 +157,7 @@ impl std::fmt::Display for Expression {
             },
             Expression::Block(tree) => {
                 write!(f, "{{ ")?;
-                for expr in tree {
+                for (expr, _) in tree {
                     write!(f, "{} ", expr)?;
                 }
                 write!(f, "}}")
@@ -71,21 +167,325 @@ impl std::fmt::Display for Expression {
     }
 }
 
+fn expect_bool(op: &str, value: DataType) -> Result<bool, LangError> {
+    match value {
+        DataType::Bool(b) => Ok(b),
+        other => Err(LangError::new(format!("Logical '{}' expects bools, got {}", op, other.type_name()))),
+    }
+}
+
+// strings compare against scalars by their printed form, scalars compare numerically
+fn values_equal(lhs: &DataType, rhs: &DataType) -> bool {
+    match (lhs, rhs) {
+        (DataType::String(l), DataType::String(r)) => l == r,
+        (DataType::String(l), DataType::Float(_) | DataType::Bool(_)) => *l == rhs.as_string(),
+        (DataType::Float(_) | DataType::Bool(_), DataType::String(r)) => lhs.as_string() == *r,
+        (DataType::Float(_) | DataType::Bool(_), DataType::Float(_) | DataType::Bool(_)) => lhs.as_float() == rhs.as_float(),
+        // maps are equal regardless of insertion order
+        (DataType::Map(l), DataType::Map(r)) => {
+            l.len() == r.len() && l.iter().all(|(key, value)| r.iter().any(|(k, v)| k == key && values_equal(value, v)))
+        },
+        _ => lhs == rhs,
+    }
+}
+
+// floats compare numerically, strings lexicographically by code point
+fn compare_values(op: &str, lhs: &DataType, rhs: &DataType) -> Result<DataType, LangError> {
+    let ordering = match (lhs, rhs) {
+        (DataType::Float(l), DataType::Float(r)) => l.partial_cmp(r),
+        (DataType::String(l), DataType::String(r)) => Some(l.cmp(r)),
+        _ => return Err(
+            LangError::new(format!("Cannot compare: \x1b[1;32m\"{} {} {}\"\x1b[0m", lhs, op, rhs))
+        ),
+    };
+
+    // NaN is unordered, every comparison with it is false
+    let result = ordering.is_some_and(|ordering| match op {
+        "<" => ordering.is_lt(),
+        ">" => ordering.is_gt(),
+        "<=" => ordering.is_le(),
+        _ => ordering.is_ge(),
+    });
+
+    Ok(DataType::Bool(result))
+}
+
+fn expect_integer(op: &str, value: &DataType) -> Result<i64, LangError> {
+    match value {
+        DataType::Float(num) if num.fract() == 0.0 => Ok(*num as i64),
+        DataType::Float(num) => Err(LangError::new(format!("Bitwise '{}' expects integers, got {}", op, num))),
+        other => Err(LangError::new(format!("Bitwise '{}' expects integers, got {}", op, other.type_name()))),
+    }
+}
+
+// arithmetic takes numbers, bools and numeric strings the same way `as_float` reads them
+fn expect_number(op: &str, value: &DataType) -> Result<f32, LangError> {
+    match value {
+        DataType::Float(_) | DataType::Bool(_) => Ok(value.as_float()),
+        DataType::String(str) => str
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| LangError::new(format!("Operator '{}' expects numbers, got {:?}", op, str))),
+        other => Err(LangError::new(format!("Operator '{}' expects numbers, got {}", op, other.type_name()))),
+    }
+}
+
+fn bitwise(op: &str, lhs: &DataType, rhs: &DataType) -> Result<DataType, LangError> {
+    let (l, r) = (expect_integer(op, lhs)?, expect_integer(op, rhs)?);
+    let result = match op {
+        "&" => l & r,
+        "|" => l | r,
+        "^" => l ^ r,
+        _ => {
+            if !(0..64).contains(&r) {
+                return Err(LangError::new(format!("Shift amount out of range: {}", r)));
+            }
+
+            if op == "<<" { l << r } else { l >> r }
+        },
+    };
+
+    Ok(DataType::Float(result as f32))
+}
+
+// `/`, `~/` and `%` refuse a zero divisor instead of producing inf/NaN
+fn divide(op: &str, lhs: &DataType, rhs: &DataType) -> Result<DataType, LangError> {
+    let (l, r) = (expect_number(op, lhs)?, expect_number(op, rhs)?);
+    if r == 0.0 {
+        return Err(LangError::new(format!("Division by zero: \x1b[1;32m\"{} {} {}\"\x1b[0m", lhs, op, rhs)));
+    }
+
+    let result = match op {
+        "/" => l / r,
+        "~/" => (l / r).floor(),
+        // floored modulo, the result takes the sign of the divisor so that a == (a ~/ b) * b + a % b
+        _ => l - (l / r).floor() * r,
+    };
+
+    Ok(DataType::Float(result))
+}
+
+// the binary operators that evaluate both sides, shared by `a op b` and `a op= b`
+fn apply_operator(op: &str, lhs: DataType, rhs: DataType) -> Result<DataType, LangError> {
+    match op {
+        "+" => {
+            if lhs.get_type() == DataTypeType::Float && rhs.get_type() == DataTypeType::Float {
+                return Ok(DataType::Float(lhs.as_float() + rhs.as_float()));
+            } else if lhs.get_type() == DataTypeType::String {
+                return Ok(DataType::String(lhs.as_string() + &rhs.as_string()));
+            }
+
+            let r_str = rhs.as_string();
+            let l_str = lhs.as_string();
+
+            Err(
+                LangError::new(format!("Invalid evaluation: \x1b[1;32m\"{} {} {}\"\x1b[0m", l_str, op, r_str))
+            )
+        },
+        "-" => Ok(DataType::Float(expect_number(op, &lhs)? - expect_number(op, &rhs)?)),
+        "*" => Ok(DataType::Float(expect_number(op, &lhs)? * expect_number(op, &rhs)?)),
+        "/" | "~/" | "%" => divide(op, &lhs, &rhs),
+        "**" => Ok(DataType::Float(expect_number(op, &lhs)?.powf(expect_number(op, &rhs)?))),
+        ".." => match (&lhs, &rhs) {
+            (DataType::String(_) | DataType::Float(_) | DataType::Bool(_), DataType::String(_) | DataType::Float(_) | DataType::Bool(_)) => {
+                Ok(DataType::String(lhs.as_string() + &rhs.as_string()))
+            },
+            _ => Err(LangError::new(format!("Cannot concatenate {} and {}", lhs.type_name(), rhs.type_name()))),
+        },
+        "&" | "|" | "^" | "<<" | ">>" => bitwise(op, &lhs, &rhs),
+        "=" => return Ok(lhs),
+        "==" => Ok(DataType::Bool(values_equal(&lhs, &rhs))),
+        "!=" => Ok(DataType::Bool(!values_equal(&lhs, &rhs))),
+        ">" | "<" | ">=" | "<=" => compare_values(op, &lhs, &rhs),
+        _ => return Err(
+            LangError::new(format!("Unsupported operator: {}, lhs: {}, rhs: {}", op, lhs, rhs))
+        ),
+    }
+}
+
+// an assignment target with every index and object in it evaluated once, so that `a[next()] += 1`
+// reads and writes the same element
+enum Place {
+    Variable(String),
+    Index(Box<Place>, DataType),
+    Field(ObjectRef, String),
+}
+
+impl Place {
+    fn resolve(target: &Expression, scopes: &mut ScopeStack) -> Result<Place, LangError> {
+        match target {
+            Expression::Identifier(name) => Ok(Place::Variable(name.clone())),
+            Expression::Index(container, index) => {
+                let container = Place::resolve(container, scopes)?;
+                Ok(Place::Index(Box::new(container), index.eval(scopes)?))
+            },
+            Expression::Member(container, name) => match container.eval(scopes)? {
+                DataType::Object(object) => {
+                    object.0.borrow().class.check_field_access(name, scopes.current_class().as_deref())?;
+                    Ok(Place::Field(object, name.clone()))
+                },
+                other => Err(LangError::new(format!("Cannot assign to member '{}' of {}", name, other.type_name()))),
+            },
+            other => Err(LangError::new(format!("Invalid assignment target: {}", other))),
+        }
+    }
+
+    fn read(&self, scopes: &mut ScopeStack) -> Result<DataType, LangError> {
+        match self {
+            Place::Variable(name) => scopes.get(name).ok_or_else(|| LangError::new(format!("Variable '{}' is not declared", name))),
+            Place::Index(container, index) => container.read(scopes)?.get_index(index),
+            Place::Field(object, name) => object.0.borrow().get_field(name),
+        }
+    }
+
+    // `a[i][j] = v` rebuilds the containers from the inside out and stores the root back through `ScopeStack::set`,
+    // objects are shared so `obj.field = v` writes straight into them
+    fn write(&self, value: DataType, scopes: &mut ScopeStack) -> Result<(), LangError> {
+        match self {
+            Place::Variable(name) => scopes.set(name, value),
+            Place::Index(container, index) => {
+                let mut current = container.read(scopes)?;
+                current.set_index(index, value)?;
+
+                container.write(current, scopes)
+            },
+            Place::Field(object, name) => object.0.borrow_mut().set_field(name, value),
+        }
+    }
+}
+
+// runs statements in the current scope, stopping early on return/break/continue
+fn run_statements(expressions: &[(Expression, Span)], scopes: &mut ScopeStack) -> Result<DataType, LangError> {
+    let mut result = DataType::EndOfBlock;
+    for (expr, span) in expressions {
+        let expr = match expr {
+            Expression::Modified(_, declaration) => declaration.as_ref(),
+            expr => expr,
+        };
+
+        if let Expression::FunctionDeclaration(fn_name, params, body, _) = expr {
+            scopes.define_function(fn_name.clone(), params.clone(), body.clone());
+        } else if let Some((var_name, expr_tree, is_declaration)) = expr.is_assign() {
+            // errors point at the innermost statement they happened in
+            let value = expr_tree.eval(scopes).map_err(|err| scopes.locate(err, *span))?;
+
+            if matches!(value, DataType::Return(_) | DataType::Break | DataType::Continue) {
+                return Ok(value);
+            }
+
+            if is_declaration {
+                scopes.declare(var_name, value.clone());
+            } else {
+                scopes.set(&var_name, value.clone()).map_err(|err| scopes.locate(err, *span))?;
+            }
+            result = value;
+        } else {
+            let val = expr.eval(scopes).map_err(|err| scopes.locate(err, *span))?;
+            if matches!(val, DataType::Return(_) | DataType::Break | DataType::Continue) {
+                return Ok(val);
+            }
+
+            result = val;
+        }
+    }
+
+    Ok(result)
+}
+
+// runs `body` on top of the scopes it was defined in, with `self` bound for methods
+fn call_function(
+    name: &str,
+    params: &[String],
+    body: &Expression,
+    env: &Environment,
+    method: Option<(Rc<ClassDef>, Option<DataType>)>, // the owner and `self` when calling a method
+    arg_values: Vec<DataType>,
+    scopes: &mut ScopeStack,
+) -> Result<DataType, LangError> {
+    if params.len() != arg_values.len() {
+        return Err(LangError::new(format!(
+            "Function '{}' expects {} arguments, got {}",
+            name, params.len(), arg_values.len()
+        )));
+    }
+
+    // private and protected members are reachable from the owner's methods and from code written inside them,
+    // never through whoever happens to be calling
+    let (context, receiver) = match method {
+        Some((owner, receiver)) => (Some(owner), receiver),
+        None => (env.class(), None),
+    };
+
+    let caller = scopes.enter(env);
+    scopes.push_scope();
+    scopes.push_class(context);
+
+    if let Some(receiver) = receiver {
+        scopes.declare("self".to_string(), receiver);
+    }
+    for (param_name, arg_value) in params.iter().zip(arg_values) {
+        scopes.declare(param_name.clone(), arg_value);
+    }
+
+    // eval body & leave scope
+    let result = body.eval(scopes);
+    scopes.pop_class();
+    scopes.pop_scope();
+    scopes.leave(caller);
+
+    match result {
+        Ok(DataType::Return(inner)) => Ok(*inner),
+        other => other,
+    }
+}
+
+// `obj.method(...)` and `Type::method(...)`, methods are looked up through the base classes
+fn call_method(target: DataType, name: &str, arg_values: Vec<DataType>, scopes: &mut ScopeStack) -> Result<DataType, LangError> {
+    let (class, receiver) = match &target {
+        DataType::Class(class) => (class.clone(), None),
+        DataType::Object(object) => (object.0.borrow().class.clone(), Some(target.clone())),
+        _ => unreachable!(),
+    };
+
+    let context = scopes.current_class();
+    let Some((owner, idx)) = ClassDef::method_owner(&class, name) else {
+        // a field holding a function is called like a method, without `self`
+        let field = match &target {
+            DataType::Object(object) => {
+                class.check_field_access(name, context.as_deref())?;
+                object.0.borrow().get_field(name).ok()
+            },
+            _ => None,
+        };
+        if let Some(DataType::Function(params, body, env)) = field {
+            return call_function(name, &params, &body, &env, None, arg_values, scopes);
+        }
+
+        return Err(LangError::new(format!("Class '{}' has no method '{}'", class.name, name)));
+    };
+
+    let method = &owner.methods[idx];
+    let qualified = format!("{}::{}", owner.name, name);
+    if !owner.allows(method.visibility, context.as_deref()) {
+        return Err(LangError::new(format!("Method '{}' is {}", qualified, method.visibility)));
+    }
+
+    match (method.is_static, &receiver) {
+        (true, Some(_)) => Err(LangError::new(format!("'{}' is static, call it as {}()", name, qualified))),
+        (false, None) => Err(LangError::new(format!("'{}' is not a static method, call it on an instance", qualified))),
+        _ => call_function(&qualified, &method.params, &method.body, &owner.env, Some((owner.clone(), receiver)), arg_values, scopes),
+    }
+}
+
 impl Expression {
     pub fn is_assign(&self) -> Option<(String, &Expression, bool)> {
         match self {
             Expression::Operation(op, tree) => {
                 if op == "=" {
                     let (var_name, is_declaration) = match tree.first().unwrap() {
-                        Expression::Declaration(var_name) => (var_name.clone(), true),
-                        Expression::Atom(var_name) => {
-                            if var_name.chars().next().map(|x| matches!(x, 'a'..='z' | 'A'..='Z')).unwrap_or(false) {
-                                (var_name.clone(), false)
-                            } else {
-                                panic!("Invalid variable name: {}", var_name);
-                            }
-                        },
-                        _ => unreachable!(),
+                        Expression::Declaration(var_name, _) => (var_name.clone(), true),
+                        Expression::Identifier(var_name) => (var_name.clone(), false),
+                        _ => return None, // rejected by the parser
                     };
 
                     return Some((var_name, tree.last().unwrap(), is_declaration));
@@ -97,6 +497,42 @@ impl Expression {
         }
     }
 
+    // the name a declaration introduces into its scope
+    pub fn declared_name(&self) -> Option<&str> {
+        match self {
+            Expression::FunctionDeclaration(name, ..)
+            | Expression::EnumDeclaration(name, _)
+            | Expression::ModuleDeclaration(name, _)
+            | Expression::Declaration(name, _) => Some(name),
+            Expression::ClassDeclaration(decl) => Some(&decl.name),
+            Expression::Operation(op, tree) if op == "=" => match &tree[0] {
+                Expression::Declaration(name, _) => Some(name),
+                _ => None,
+            },
+            Expression::Modified(_, declaration) => declaration.declared_name(),
+            _ => None,
+        }
+    }
+
+    // names a module body marks `public`, everything else stays private to the module
+    pub fn exports(body: &[(Expression, Span)]) -> Result<Vec<String>, LangError> {
+        let mut exports = Vec::new();
+        for (expr, _) in body {
+            if let Expression::Modified(visibility, declaration) = expr {
+                let name = declaration.declared_name().unwrap_or_default();
+                match visibility {
+                    Visibility::Public => exports.push(name.to_string()),
+                    Visibility::Private => {},
+                    Visibility::Protected => {
+                        return Err(LangError::new(format!("'protected' only applies to class members, not module member '{}'", name)));
+                    },
+                }
+            }
+        }
+
+        Ok(exports)
+    }
+
     pub fn is_block(&self) -> bool {
         matches!(self, Expression::Block(_))
     }
@@ -111,6 +547,35 @@ impl Expression {
 
     pub fn eval(&self, scopes: &mut ScopeStack) -> Result<DataType, LangError> {
         match self {
+            Expression::Unary(op, operand) => {
+                let value = operand.eval(scopes)?;
+                match (op.as_str(), &value) {
+                    ("-", DataType::Float(num)) => Ok(DataType::Float(-num)),
+                    ("!", DataType::Bool(b)) => Ok(DataType::Bool(!b)),
+                    ("~", DataType::Float(num)) if num.fract() == 0.0 => Ok(DataType::Float(!(*num as i64) as f32)),
+                    ("~", DataType::Float(num)) => Err(LangError::new(format!("Bitwise not expects an integer, got {}", num))),
+                    ("-", _) | ("~", _) => Err(LangError::new(format!("Unary '{}' expects a float, got {}", op, value.type_name()))),
+                    ("!", _) => Err(LangError::new(format!("Logical not expects a bool, got {}", value.type_name()))),
+                    _ => Err(LangError::new(format!("Unsupported unary operator: {}", op))),
+                }
+            },
+            Expression::Interpolation(segments) => {
+                let mut result = String::new();
+                for segment in segments {
+                    result.push_str(&segment.eval(scopes)?.as_string());
+                }
+
+                Ok(DataType::String(result))
+            },
+            Expression::Map(entries) => {
+                let mut map = DataType::Map(Vec::new());
+                for (key, value) in entries {
+                    let value = value.eval(scopes)?;
+                    map.set_index(&DataType::String(key.clone()), value)?;
+                }
+
+                Ok(map)
+            },
             Expression::Array(elements) => {
                 let evaluated_elements = elements.iter().map(|expr| expr.eval(scopes)).collect::<Result<Vec<_>, _>>()?;
                 Ok(DataType::Array(evaluated_elements))
@@ -192,7 +657,7 @@ impl Expression {
                 loop {
                     let cond_val = condition.eval(scopes)?;
                     
-                    if !cond_val.is_truthy() {
+                    if !cond_val.is_truthy()? {
                         break;
                     }
                     match body.eval(scopes) {
@@ -256,7 +721,7 @@ impl Expression {
                 scopes.pop_scope();
                 Ok(result)
             },
-            Expression::FunctionCall(fn_name, args) => {
+            Expression::FunctionCall(callee, args) => {
                 let mut arg_values = Vec::new();
                 for arg in args.iter() {
                     match arg.eval(scopes) {
@@ -265,129 +730,201 @@ impl Expression {
                     }
                 }
 
-                if let Some(scope_registry) = scopes.get_native_registry() {                    
-                    if scope_registry.has(&fn_name) {
-                        return scope_registry.call(&fn_name, &arg_values);
-                    }
-                }
-
-                let (params, body) = match scopes.get(&fn_name) {
-                    Some(fn_data) => {
-                        match fn_data {
-                            DataType::Function(params, body) => {
-                                if params.len() != args.len() {
-                                    return Err(LangError::new(format!(
-                                        "Function '{}' expects {} arguments, got {}",
-                                        fn_name, params.len(), args.len()
-                                    )));
-                                }
-                                (params.clone(), body.clone())
-                            }
-                            _ => return Err(LangError::new(format!("'{}' is not a function", fn_name)))
+                // plain names are looked up in the scopes and fall back to the natives, so a user
+                // definition shadows a native of the same name. anything else is evaluated to a function value
+                let function = match callee.as_ref() {
+                    Expression::Identifier(fn_name) => match scopes.get(fn_name) {
+                        Some(fn_data) => fn_data,
+                        None => match scopes.get_native_registry() {
+                            Some(scope_registry) if scope_registry.has(fn_name) => return scope_registry.call(fn_name, &arg_values),
+                            _ => return Err(LangError::new(format!("Function '{}' is not defined", fn_name))),
+                        },
+                    },
+                    // `Enum.Variant(...)` builds a value, classes and objects dispatch to their methods
+                    Expression::Member(target, name) => {
+                        let target = target.eval(scopes)?;
+                        match &target {
+                            DataType::Enum(def) => return def.construct(name, arg_values),
+                            DataType::Class(_) | DataType::Object(_) => return call_method(target, name, arg_values, scopes),
+                            _ => target.get_member(name)?,
                         }
                     },
-                    None => return Err(LangError::new(format!("Function '{}' is not defined", fn_name))),
+                    other => other.eval(scopes)?,
                 };
-                
-                scopes.push_scope();
-                
-                for (param_name, arg_value) in params.iter().zip(arg_values.iter()) {
-                    scopes.declare(param_name.clone(), arg_value.clone());
-                }
-                
-                // eval body & leave scope
-                let result = body.eval(scopes);
-                scopes.pop_scope();
-                
-                match result {
-                    Ok(DataType::Return(inner) )=> Ok(*inner),
-                    Ok(other) => Ok(other),
-                    Err(e) => Err(e),
+
+                // the body sees the scopes it was defined in, not the caller's
+                match function {
+                    DataType::Function(params, body, env) => {
+                        call_function(&callee.to_string(), &params, &body, &env, None, arg_values, scopes)
+                    },
+                    DataType::NativeFunction(name) => match scopes.get_native_registry() {
+                        Some(registry) => registry.call(&name, &arg_values),
+                        None => Err(LangError::new(format!("Native function '{}' not found", name))),
+                    },
+                    _ => Err(LangError::new(format!("'{}' is not a function", callee)))
                 }
             },
-            Expression::Declaration(decl) | Expression::FunctionDeclaration(decl, ..)  => return Err(
+            Expression::Declaration(decl, _) | Expression::FunctionDeclaration(decl, ..)  => return Err(
                 LangError::new(format!("Cannot evaluate declaration: {}", decl))
             ),
-            Expression::Atom(val) => {
-                if val == "true" {
-                    return Ok(DataType::Bool(true));
+            Expression::Literal(literal) => Ok(match literal {
+                Literal::Number(num) => DataType::Float(*num),
+                Literal::Bool(b) => DataType::Bool(*b),
+                Literal::String(str) => DataType::String(str.clone()),
+            }),
+            Expression::Closure(params, body, _) => Ok(DataType::Function(params.clone(), (**body).clone(), scopes.capture())),
+            Expression::EnumDeclaration(name, variants) => {
+                let def = EnumDef { name: name.clone(), variants: variants.clone() };
+                scopes.declare(name.clone(), DataType::Enum(Rc::new(def)));
+
+                Ok(DataType::EndOfBlock)
+            },
+            Expression::Member(target, name) => {
+                let target = target.eval(scopes)?;
+                if let DataType::Object(object) = &target {
+                    object.0.borrow().class.check_field_access(name, scopes.current_class().as_deref())?;
+                }
+
+                target.get_member(name)
+            },
+            Expression::ClassDeclaration(decl) => {
+                let base = match &decl.base {
+                    None => None,
+                    Some(base) => match scopes.get(base) {
+                        Some(DataType::Class(class)) => Some(class),
+                        Some(other) => return Err(LangError::new(format!("Cannot inherit from '{}', it is a {}", base, other.type_name()))),
+                        None => return Err(LangError::new(format!("Base class '{}' is not defined", base))),
+                    },
+                };
+
+                let class = ClassDef {
+                    name: decl.name.clone(),
+                    base,
+                    fields: decl.fields.iter().map(|field| (field.name.clone(), field.visibility)).collect(),
+                    methods: decl.methods.clone(),
+                    env: scopes.capture(),
+                };
+                scopes.declare(decl.name.clone(), DataType::Class(Rc::new(class)));
+
+                Ok(DataType::EndOfBlock)
+            },
+            Expression::StructLiteral(class_name, entries) => {
+                let class = match scopes.get(class_name) {
+                    Some(DataType::Class(class)) => class,
+                    Some(other) => return Err(LangError::new(format!("'{}' is a {}, not a class", class_name, other.type_name()))),
+                    None => return Err(LangError::new(format!("Class '{}' is not defined", class_name))),
+                };
+
+                let mut values = Vec::new();
+                for (field, value) in entries {
+                    values.push((field.clone(), value.eval(scopes)?));
                 }
-                if val == "false" {
-                    return Ok(DataType::Bool(false));
+
+                ClassDef::instantiate(&class, values)
+            },
+            Expression::Identifier(name) => match scopes.get(name) {
+                Some(value) => Ok(value),
+                None if scopes.get_native_registry().is_some_and(|registry| registry.has(name)) => {
+                    Ok(DataType::NativeFunction(name.clone()))
+                },
+                None => Err(LangError::new(format!("Variable '{}' is not defined", name))),
+            },
+            // an imported file runs in its own scope with only `std` in sight and becomes a module
+            Expression::Import(name, path, body) => {
+                // a file runs once, importing it again shares the module it made
+                if let Some(module) = scopes.imported(path) {
+                    scopes.declare(name.clone(), DataType::Module(module));
+                    return Ok(DataType::EndOfBlock);
                 }
 
-                if let Ok(num) = val.parse::<f32>() {
-                    return Ok(DataType::Float(num));
+                let exports = Expression::exports(body)?;
+                let std = scopes.get("std");
+                let caller = scopes.enter(&Environment::empty(path));
+                if let Some(std) = std {
+                    scopes.declare("std".to_string(), std);
                 }
 
-                if let Some(value) = scopes.get(val) {
-                    return Ok(value.clone());
+                let result = run_statements(body, scopes);
+                let scope = scopes.pop_scope_shared();
+                scopes.leave(caller);
+
+                if matches!(result?, DataType::Return(_) | DataType::Break | DataType::Continue) {
+                    return Err(LangError::new(format!("return, break and continue are not allowed at the top of module '{}'", name)));
                 }
 
-                if val.starts_with("\"") && val.ends_with("\"") {
-                    return Ok(DataType::String(val[1..val.len()-1].to_string()));
+                let module = Rc::new(Module { name: name.clone(), scope, exports });
+                scopes.remember_import(path.clone(), module.clone());
+                scopes.declare(name.clone(), DataType::Module(module));
+                Ok(DataType::EndOfBlock)
+            },
+            // visibility only matters to whoever looks the name up later
+            Expression::Modified(_, declaration) => declaration.eval(scopes),
+            Expression::Using(path) => match path.eval(scopes)? {
+                DataType::Module(module) => {
+                    for (name, value) in module.scope.borrow().entries() {
+                        if module.exports.contains(&name) {
+                            scopes.declare(name, value);
+                        }
+                    }
+                    Ok(DataType::EndOfBlock)
+                },
+                other => Err(LangError::new(format!("'using' expects a module, got {}", other.type_name()))),
+            },
+            Expression::ModuleDeclaration(name, body) => {
+                let exports = Expression::exports(body)?;
+                scopes.push_scope();
+                let result = run_statements(body, scopes);
+                let scope = scopes.pop_scope_shared();
+
+                if matches!(result?, DataType::Return(_) | DataType::Break | DataType::Continue) {
+                    return Err(LangError::new(format!("return, break and continue are not allowed at the top of module '{}'", name)));
                 }
-                
-                Err(LangError::new(format!("Variable '{}' is not defined", val)))
+
+                scopes.declare(name.clone(), DataType::Module(Rc::new(Module { name: name.clone(), scope, exports })));
+                Ok(DataType::EndOfBlock)
+            },
+            Expression::Operation(op, tree) if op == "=" && matches!(tree[0], Expression::Index(..) | Expression::Member(..)) => {
+                let value = tree[1].eval(scopes)?;
+                Place::resolve(&tree[0], scopes)?.write(value.clone(), scopes)?;
+
+                Ok(value)
+            },
+            Expression::CompoundAssignment(op, target, value) => {
+                let place = Place::resolve(target, scopes)?;
+                let current = place.read(scopes)?;
+                let value = apply_operator(op, current, value.eval(scopes)?)?;
+                place.write(value, scopes)?;
+
+                Ok(DataType::EndOfBlock)
+            },
+            Expression::Index(target, index) => {
+                let target = target.eval(scopes)?;
+                let index = index.eval(scopes)?;
+
+                target.get_index(&index)
+            },
+            Expression::Slice(target, start, end) => {
+                let target = target.eval(scopes)?;
+                let start = start.as_ref().map(|s| s.eval(scopes)).transpose()?;
+                let end = end.as_ref().map(|e| e.eval(scopes)).transpose()?;
+
+                target.slice(start.as_ref(), end.as_ref())
+            },
+            Expression::Operation(op, tree) if op == "&&" || op == "||" => {
+                let lhs = expect_bool(op, tree[0].eval(scopes)?)?;
+
+                // `false && x` and `true || x` never evaluate x
+                let short_circuits = if op == "&&" { !lhs } else { lhs };
+                if short_circuits {
+                    return Ok(DataType::Bool(lhs));
+                }
+
+                Ok(DataType::Bool(expect_bool(op, tree[1].eval(scopes)?)?))
             },
             Expression::Operation(op, tree) => {
                 match tree.first().unwrap().eval(scopes) {
                     Ok(lhs) => match tree.last().unwrap().eval(scopes) {
-                        Ok(rhs) => {
-                            match op.as_str() {
-                                "+" => {
-                                    if lhs.get_type() == DataTypeType::Float && rhs.get_type() == DataTypeType::Float {
-                                        return Ok(DataType::Float(lhs.as_float() + rhs.as_float()));
-                                    } else if lhs.get_type() == DataTypeType::String {
-                                        return Ok(DataType::String(lhs.as_string() + &rhs.as_string()));
-                                    }
-
-                                    let r_str = rhs.as_string();
-                                    let l_str = lhs.as_string();
-
-                                    Err(
-                                        LangError::new(format!("Invalid evaluation: \x1b[1;32m\"{} {} {}\"\x1b[0m", l_str, op, r_str))
-                                    )
-                                },
-                                "-" => return Ok(DataType::Float(lhs.as_float() - rhs.as_float())),
-                                "*" => return Ok(DataType::Float(lhs.as_float() * rhs.as_float())),
-                                "/" => return Ok(DataType::Float(lhs.as_float() / rhs.as_float())),
-                                "=" => return Ok(lhs),
-                                "==" => {
-                                    if lhs.get_type() == DataTypeType::String {
-                                        return Ok(DataType::Bool(lhs.as_string() == rhs.as_string()));
-                                    } else {
-                                        return Ok(DataType::Bool(lhs.as_float() == rhs.as_float()));
-                                    }
-                                }
-                                ">" => {
-                                    if lhs.get_type() == DataTypeType::Float && rhs.get_type() == DataTypeType::Float {
-                                        return Ok(DataType::Bool(lhs.as_float() > rhs.as_float()));
-                                    }
-
-                                    return Err(
-                                        LangError::new(format!("Cannot compare: \x1b[1;32m\"{} > {}\"\x1b[0m", lhs, rhs))
-                                    )
-                                },
-                                "<" => {
-                                    if lhs.get_type() == DataTypeType::Float && rhs.get_type() == DataTypeType::Float {
-                                        return Ok(DataType::Bool(lhs.as_float() < rhs.as_float()));
-                                    }
-
-                                    return Err(
-                                        LangError::new(format!("Cannot compare: \x1b[1;32m\"{} > {}\"\x1b[0m", lhs, rhs))
-                                    )
-                                },
-                                /*"!=" => if lhs != rhs { 1.0 } else { 0.0 },
-                                ">=" => if lhs >= rhs { 1.0 } else { 0.0 },
-                                "<=" => if lhs <= rhs { 1.0 } else { 0.0 },
-                                "&&" => if lhs != 0.0 && rhs != 0.0 { 1.0 } else { 0.0 },
-                                "||" => if lhs != 0.0 || rhs != 0.0 { 1.0 } else { 0.0 }, */
-                                _ => return Err(
-                                    LangError::new(format!("Unsupported operator: {}, lhs: {}, rhs: {}", op, lhs, rhs))
-                                ),
-                            }
-                        },
+                        Ok(rhs) => apply_operator(op, lhs, rhs),
                         Err(err) => return Err(err),
                     },
                     Err(err) => return Err(err),
@@ -396,7 +933,7 @@ impl Expression {
             Expression::If(condition, then_body, elseif_branches, else_body) => {
                 match condition.eval(scopes) {
                     Ok(cond_val) => {
-                        if cond_val.is_truthy() {
+                        if cond_val.is_truthy()? {
                             let result = then_body.eval(scopes)?;
                             
                             if matches!(result, DataType::Return(_) | DataType::Break | DataType::Continue) {
@@ -410,7 +947,7 @@ impl Expression {
                         for (elseif_cond, elseif_body) in elseif_branches {
                             let elseif_val = elseif_cond.eval(scopes)?;
                             
-                            if elseif_val.is_truthy() {
+                            if elseif_val.is_truthy()? {
                                 let result = elseif_body.eval(scopes)?;
                                 
                                 if matches!(result, DataType::Return(_) | DataType::Break | DataType::Continue) {
@@ -438,47 +975,136 @@ impl Expression {
             },
             Expression::Block(expressions) => {
                 scopes.push_scope();
-                
-                let mut result = DataType::EndOfBlock;
-                for expr in expressions {
-                    if let Expression::FunctionDeclaration(fn_name, params, body) = expr {
-                        scopes.define_function(fn_name.clone(), params.clone(), body.clone());
-                    } else if let Some((var_name, expr_tree, is_declaration)) = expr.is_assign() {
-                        let value = expr_tree.eval(scopes)?;
-                        
-                        if matches!(value, DataType::Return(_) | DataType::Break | DataType::Continue) {
-                            scopes.pop_scope();
-                            return Ok(value);
-                        }
-                        
-                        if is_declaration {
-                            scopes.declare(var_name, value.clone());
-                            result = value;
-                        } else {
-                            scopes.set(&var_name, value.clone())?;
-                            result = value;
-                        }
-                    } else {
-                        match expr.eval(scopes) {
-                            Ok(val) => {
-                                if matches!(val, DataType::Return(_) | DataType::Break | DataType::Continue) {
-                                    scopes.pop_scope();
-                                    return Ok(val);
-                                }
-                                
-                                result = val;
-                            },
-                            Err(err) => {
-                                scopes.pop_scope();
-                                return Err(err);
-                            }
-                        }
-                    }
-                }
-                
+                let result = run_statements(expressions, scopes);
                 scopes.pop_scope();
-                Ok(result)
+
+                result
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::language::datatypes::DataType;
+    use crate::language::errors::LangError;
+    use crate::language::tokens::Program;
+
+    fn run(source: &str) -> Result<Program, LangError> {
+        let mut program = Program::new();
+        program.tokenize(source)?;
+        program.begin().map_err(|mut errors| errors.remove(0))?;
+        Ok(program)
+    }
+
+    fn value_of(source: &str, name: &str) -> DataType {
+        let mut program = run(source).unwrap();
+        program.scopes.get(name).unwrap()
+    }
+
+    fn error_of(source: &str) -> String {
+        run(source).err().expect("the program should fail").message
+    }
+
+    fn error_line(source: &str) -> usize {
+        run(source).err().and_then(|err| err.span).expect("the program should fail at a statement").line
+    }
+
+    #[test]
+    fn arithmetic_on_numbers() {
+        assert_eq!(value_of("let x = 5 - 2 * 3;", "x"), DataType::Float(-1.0));
+        assert_eq!(value_of("let x = 7 ~/ 2 + 7 % 2 + 2 ** 3;", "x"), DataType::Float(12.0));
+    }
+
+    #[test]
+    fn arithmetic_rejects_non_numbers() {
+        assert_eq!(error_of("let a = [1] - 1;"), "Operator '-' expects numbers, got array");
+        assert_eq!(error_of("let a = [1, 2] * 2;"), "Operator '*' expects numbers, got array");
+        assert_eq!(error_of("let a = \"a\" - 1;"), "Operator '-' expects numbers, got \"a\"");
+        assert_eq!(error_of("let a = {} % 2;"), "Operator '%' expects numbers, got map");
+    }
+
+    #[test]
+    fn runtime_errors_point_at_their_own_statement() {
+        assert_eq!(error_line("if true {\n  let a = 1;\n\n  print(nope);\n}"), 4);
+        assert_eq!(error_line("function f(xs) {\n  let n = 1;\n  return xs[5];\n}\n\nprint(f([1, 2]));"), 3);
+        assert_eq!(error_line("let i = 0;\nwhile i < 3 {\n  i += 1;\n  if i == 2 {\n    i = i - [];\n  }\n}"), 5);
+        // expressions without statements of their own are reported at the call
+        assert_eq!(error_line("let f = (x) => x[3];\n\nf([]);"), 3);
+    }
+
+    #[test]
+    fn compound_assignment_evaluates_the_target_once() {
+        let source = "let n = 0;\nfunction next() { n += 1; return n; }\nlet a = [10, 20, 30];\na[next()] += 5;";
+        let mut program = run(source).unwrap();
+        assert_eq!(program.scopes.get("n"), Some(DataType::Float(1.0)));
+        assert_eq!(program.scopes.get("a"), Some(DataType::Array(vec![DataType::Float(10.0), DataType::Float(25.0), DataType::Float(30.0)])));
+    }
+
+    #[test]
+    fn compound_assignment_on_index_and_member_targets() {
+        let matrix = value_of("let m = [[1, 2], [3, 4]];\nm[1][0] *= 10;\nm[0][1]++;\nm[1][1]--;\nlet x = m[0][1] + m[1][0] + m[1][1];", "x");
+        assert_eq!(matrix, DataType::Float(36.0));
+
+        let class = "class C { public v: float, public s: string }\nlet c = C { v: 1, s: \"a\" };\n";
+        assert_eq!(value_of(&format!("{}c.v -= 3;\nlet cs = [c];\ncs[0].v++;\nlet x = c.v;", class), "x"), DataType::Float(-1.0));
+        assert_eq!(value_of(&format!("{}c.s ..= \"b\";\nlet x = c.s;", class), "x"), DataType::String("ab".to_string()));
+        assert_eq!(value_of("let m = { k: 1 };\nm[\"k\"] += 1;\nlet x = m[\"k\"];", "x"), DataType::Float(2.0));
+    }
+
+    #[test]
+    fn compound_assignment_needs_a_declared_target() {
+        assert_eq!(error_of("nope += 1;"), "Variable 'nope' is not declared");
+        assert_eq!(error_of("let a = [1];\na[3] += 1;"), "Index 3 out of bounds for length 1");
+    }
+
+    const ACCOUNT: &str = "
+class Account {
+  public owner: string,
+  private balance: float,
+  protected kind: string,
+
+  public static method open(o) { return Account { owner: o, balance: 0, kind: \"basic\" }; }
+  public method deposit(n) { self.balance += n; return self.audit(); }
+  public method reader() { return (o) => o.balance; }
+  public method leak(f) { return f(self); }
+  private method audit() { return self.balance; }
+  protected method label() { return self.kind; }
+}
+
+class Savings : Account {
+  public method describe() { return self.label() .. \" \" .. self.kind; }
+}
+
+function peek(o) { return o.balance; }
+let a = Account::open(\"ann\");
+";
+
+    #[test]
+    fn members_are_reachable_from_their_class() {
+        assert_eq!(value_of(&format!("{}let x = a.deposit(5) + a.deposit(2);", ACCOUNT), "x"), DataType::Float(12.0));
+        assert_eq!(value_of(&format!("{}let s = Savings {{ owner: \"bo\", balance: 0, kind: \"savings\" }}.describe();", ACCOUNT), "s"), DataType::String("savings savings".to_string()));
+        // a closure written inside a method keeps its access wherever it is called from
+        assert_eq!(value_of(&format!("{}a.deposit(3);\nlet x = a.reader()(a);", ACCOUNT), "x"), DataType::Float(3.0));
+    }
+
+    #[test]
+    fn members_are_hidden_from_outside_code() {
+        assert_eq!(error_of(&format!("{}let x = a.balance;", ACCOUNT)), "Field 'balance' of 'Account' is private");
+        assert_eq!(error_of(&format!("{}let x = a.audit();", ACCOUNT)), "Method 'Account::audit' is private");
+        assert_eq!(error_of(&format!("{}let x = a.label();", ACCOUNT)), "Method 'Account::label' is protected");
+        assert_eq!(error_of(&format!("{}a.balance = 3;", ACCOUNT)), "Field 'balance' of 'Account' is private");
+    }
+
+    #[test]
+    fn functions_called_from_a_method_get_no_access() {
+        assert_eq!(error_of(&format!("{}let x = a.leak(peek);", ACCOUNT)), "Field 'balance' of 'Account' is private");
+    }
+
+    #[test]
+    fn module_members_are_private_by_default() {
+        let source = "module Shop {\n  let secret = 42;\n  public function price() { return secret * 2; }\n}\n";
+        assert_eq!(value_of(&format!("{}let p = Shop::price();", source), "p"), DataType::Float(84.0));
+        assert_eq!(error_of(&format!("{}let s = Shop::secret;", source)), "'secret' is private to module 'Shop'");
+    }
+}
@@ -0,0 +1,230 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, rc::Rc};
+
+use crate::files::FILE_EXTENSION;
+use crate::language::expressions::Expression;
+use crate::language::span::Span;
+
+// finds imported files and keeps every file parsed at most once for the whole program
+pub struct ModuleLoader {
+    search_paths: Vec<PathBuf>,
+    parsed: HashMap<PathBuf, Rc<Vec<(Expression, Span)>>>,
+    sources: Vec<(PathBuf, String)>, // every file read, in the order they were read, for checking and error messages
+    chain: Vec<PathBuf>, // files currently being parsed, outermost first
+}
+
+impl ModuleLoader {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        ModuleLoader {
+            search_paths,
+            parsed: HashMap::new(),
+            sources: Vec::new(),
+            chain: Vec::new(),
+        }
+    }
+
+    // `"./util.nx"` is looked up next to the importing file only, `"util.nx"` and `util.strings`
+    // next to it first and then in each search path
+    pub fn resolve(&self, spec: &str, is_path: bool, from: Option<&Path>) -> Result<PathBuf, String> {
+        let mut relative = if is_path {
+            PathBuf::from(spec)
+        } else {
+            spec.split('.').collect::<PathBuf>()
+        };
+        if relative.extension().is_none() {
+            relative.set_extension(FILE_EXTENSION.trim_start_matches('.'));
+        }
+
+        let importing_dir = from.and_then(|file| file.parent()).map(Path::to_path_buf).unwrap_or_default();
+        let mut candidates = vec![importing_dir.join(&relative)];
+        if !(is_path && (spec.starts_with("./") || spec.starts_with("../"))) {
+            candidates.extend(self.search_paths.iter().map(|dir| dir.join(&relative)));
+        }
+
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_file())
+            .map(|found| found.canonicalize().unwrap_or_else(|_| found.clone()))
+            .ok_or_else(|| {
+                let tried = candidates.iter().map(|c| c.display().to_string()).collect::<Vec<_>>().join(", ");
+                format!("Cannot find module '{}' (tried {})", spec, tried)
+            })
+    }
+
+    pub fn cached(&self, path: &Path) -> Option<Rc<Vec<(Expression, Span)>>> {
+        self.parsed.get(path).cloned()
+    }
+
+    // marks `path` as being parsed, failing with the whole chain if it already is
+    pub fn enter(&mut self, path: PathBuf) -> Result<(), String> {
+        if let Some(start) = self.chain.iter().position(|file| *file == path) {
+            let cycle = self.chain[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|file| file.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(format!("Import cycle: {}", cycle));
+        }
+
+        self.chain.push(path);
+        Ok(())
+    }
+
+    pub fn leave(&mut self, path: &Path, body: Option<Rc<Vec<(Expression, Span)>>>) {
+        self.chain.retain(|file| file != path);
+        if let Some(body) = body {
+            self.parsed.insert(path.to_path_buf(), body);
+        }
+    }
+
+    pub fn add_source(&mut self, path: &Path, source: String) {
+        self.sources.push((path.to_path_buf(), source));
+    }
+
+    pub fn source(&self, path: &Path) -> Option<&str> {
+        self.sources.iter().find(|(file, _)| file == path).map(|(_, source)| source.as_str())
+    }
+
+    // every imported file that parsed, with its statements
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &[(Expression, Span)])> {
+        self.sources
+            .iter()
+            .filter_map(|(path, _)| self.parsed.get(path).map(|body| (path.as_path(), body.as_slice())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs};
+
+    use super::*;
+    use crate::language::datatypes::DataType;
+    use crate::language::errors::LangError;
+    use crate::language::tokens::Program;
+
+    // a fresh directory holding `files`, removed again when the test is done
+    struct Project(PathBuf);
+
+    impl Project {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("nexen-loader-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (file, source) in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, source).unwrap();
+            }
+
+            Project(root.canonicalize().unwrap())
+        }
+
+        fn path(&self, file: &str) -> PathBuf {
+            self.0.join(file)
+        }
+
+        fn run(&self, file: &str, search_paths: Vec<PathBuf>) -> Result<Program, Vec<LangError>> {
+            let path = self.path(file);
+            let mut program = Program::new();
+            let mut loader = ModuleLoader::new(search_paths);
+            loader.enter(path.clone()).unwrap();
+            program.loader = Rc::new(RefCell::new(loader));
+            program.path = Some(path.clone());
+
+            program.tokenize(&fs::read_to_string(&path).unwrap()).map_err(|err| vec![err])?;
+            program.begin()?;
+            Ok(program)
+        }
+    }
+
+    impl Drop for Project {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn resolves_next_to_the_importer_then_in_search_paths() {
+        let project = Project::new("resolve", &[
+            ("app/main.nx", ""),
+            ("app/util.nx", ""),
+            ("lib/text/strings.nx", ""),
+        ]);
+        let loader = ModuleLoader::new(vec![project.path("lib")]);
+        let main = project.path("app/main.nx");
+
+        assert_eq!(loader.resolve("./util.nx", true, Some(&main)), Ok(project.path("app/util.nx")));
+        assert_eq!(loader.resolve("util", false, Some(&main)), Ok(project.path("app/util.nx")));
+        assert_eq!(loader.resolve("text.strings", false, Some(&main)), Ok(project.path("lib/text/strings.nx")));
+
+        // `./` never falls back to the search paths
+        let missing = loader.resolve("./text/strings.nx", true, Some(&main)).unwrap_err();
+        assert!(missing.starts_with("Cannot find module './text/strings.nx'"), "{}", missing);
+    }
+
+    #[test]
+    fn a_file_imported_twice_runs_once() {
+        let project = Project::new("shared", &[
+            ("shared.nx", "let count = 0;\npublic function bump() { count = count + 1; return count; }\n"),
+            ("a.nx", "import \"./shared.nx\";\npublic function a() { return shared::bump(); }\n"),
+            ("b.nx", "import \"./shared.nx\";\npublic function b() { return shared::bump(); }\n"),
+            ("main.nx", "import \"./a.nx\";\nimport \"./b.nx\";\nimport \"./shared.nx\";\nlet counts = [a::a(), b::b(), shared::bump()];\n"),
+        ]);
+
+        let mut program = project.run("main.nx", Vec::new()).unwrap();
+        let counts = (1..=3).map(|n| DataType::Float(n as f32)).collect();
+        assert_eq!(program.scopes.get("counts"), Some(DataType::Array(counts)));
+    }
+
+    // (file name, line, message) of every error
+    fn located(errors: Vec<LangError>) -> Vec<(String, usize, String)> {
+        errors
+            .into_iter()
+            .map(|err| {
+                let file = err.file.as_ref().and_then(|file| file.file_name()).map(|name| name.to_string_lossy().to_string());
+                (file.unwrap_or_default(), err.span.map_or(0, |span| span.line), err.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_import_cycles_in_the_file_that_closes_them() {
+        let project = Project::new("cycle", &[
+            ("main.nx", "import \"./b.nx\";\n"),
+            ("b.nx", "let x = 1;\nimport \"./c.nx\";\n"),
+            ("c.nx", "\nimport \"./b.nx\";\n"),
+        ]);
+
+        let errors = located(project.run("main.nx", Vec::new()).err().unwrap());
+        let cycle = format!("Import cycle: {} -> {} -> {}", project.path("b.nx").display(), project.path("c.nx").display(), project.path("b.nx").display());
+        assert_eq!(errors, [("c.nx".to_string(), 2, cycle)]);
+    }
+
+    #[test]
+    fn syntax_errors_in_imports_keep_their_file() {
+        let project = Project::new("syntax", &[
+            ("main.nx", "import \"./lib/util.nx\";\n"),
+            ("lib/util.nx", "let a = ;\nlet b = 2;\nlet c = (1;\n"),
+        ]);
+
+        let errors = located(project.run("main.nx", Vec::new()).err().unwrap());
+        assert_eq!(errors, [
+            ("util.nx".to_string(), 1, "Expected expression, got ';'".to_string()),
+            ("util.nx".to_string(), 3, "Expected ')', got: EndExpressionToken(\";\")".to_string()),
+        ]);
+
+        let missing = located(Project::new("missing", &[("main.nx", "let a = 1;\nimport \"./nope.nx\";\n")]).run("main.nx", Vec::new()).err().unwrap());
+        assert_eq!((missing[0].0.as_str(), missing[0].1), ("", 2));
+        assert!(missing[0].2.starts_with("Cannot find module './nope.nx'"));
+    }
+
+    #[test]
+    fn runtime_errors_in_imported_code_keep_their_file() {
+        let project = Project::new("runtime", &[
+            ("lib.nx", "public function boom(xs) {\n  let n = 1;\n  return xs[5];\n}\n"),
+            ("main.nx", "import \"./lib.nx\";\n\nlib::boom([1]);\n"),
+        ]);
+
+        let errors = located(project.run("main.nx", Vec::new()).err().unwrap());
+        assert_eq!(errors, [("lib.nx".to_string(), 3, "Index 5 out of bounds for length 1".to_string())]);
+    }
+}
@@ -1,6 +1,10 @@
+use std::path::PathBuf;
 use std::time::Instant;
 
-use crate::language::errors::ProgramError;
+use crate::language::checker::Checker;
+use crate::language::errors::{LangError, ProgramError};
+use crate::language::loader::ModuleLoader;
+use crate::language::span::Span;
 
 pub mod tokens;
 pub mod datatypes;
@@ -11,25 +15,83 @@ pub mod binder;
 pub mod stdlib;
 pub mod vm;
 pub mod tokenizer;
+pub mod span;
+pub mod classes;
+pub mod loader;
+pub mod checker;
 
-pub fn interpret(source: String) -> Result<std::time::Duration, errors::ProgramError> {
+// `file_path` is None for source given on the command line, its imports resolve from the working directory
+fn load_program(source: &str, file_path: Option<&str>, search_paths: Vec<PathBuf>) -> Result<tokens::Program, Vec<errors::ProgramError>> {
     let mut program = tokens::Program::new();
-    program.tokenize(&source);
+    let mut loader = ModuleLoader::new(search_paths);
+    if let Some(file_path) = file_path {
+        let path = PathBuf::from(file_path);
+        let path = path.canonicalize().unwrap_or(path);
+        // the entry file takes part in cycle detection like any import
+        let _ = loader.enter(path.clone());
+        program.path = Some(path);
+    }
+    program.loader = std::rc::Rc::new(std::cell::RefCell::new(loader));
+
+    if let Err(err) = program.tokenize(source) {
+        return Err(vec![to_program_error(&program, source, err)]);
+    }
+
+    Ok(program)
+}
+
+pub fn interpret(source: String, file_path: Option<&str>, search_paths: Vec<PathBuf>) -> Result<std::time::Duration, Vec<errors::ProgramError>> {
+    let mut program = load_program(&source, file_path, search_paths)?;
 
+    // only syntax errors stop a run up front, type annotations are checked by `check` alone
     let program_time = Instant::now();
     match program.begin() {
-        Err(err) => {
-            let code_at_line = source.lines().nth(program.current_line - 1).unwrap_or("");
-            
-            Err(ProgramError::new(err.message, program.current_line, code_at_line.to_string()))
-        }
+        Err(errors) => Err(errors
+            .into_iter()
+            .map(|err| to_program_error(&program, &source, err))
+            .collect()),
         Ok(_) => return Ok(program_time.elapsed()),
     }
 }
 
+// syntax errors and type diagnostics for the program and everything it imports, without running anything
+pub fn check(source: String, file_path: Option<&str>, search_paths: Vec<PathBuf>) -> Result<(), Vec<errors::ProgramError>> {
+    let mut program = load_program(&source, file_path, search_paths)?;
+    let mut errors: Vec<ProgramError> = match program.check() {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.into_iter().map(|err| to_program_error(&program, &source, err)).collect(),
+    };
+
+    // imported files are checked on their own so their diagnostics quote their own lines
+    for (path, statements) in program.loader.borrow().files() {
+        let diagnostics = Checker::check(statements, &program.registry);
+        errors.extend(diagnostics.into_iter().map(|err| to_program_error(&program, &source, err.in_file(path))));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+// errors tagged with an imported file are shown against that file's source
+fn to_program_error(program: &tokens::Program, source: &str, err: LangError) -> ProgramError {
+    let loader = program.loader.borrow();
+    let (source, fallback) = match err.file.as_deref().and_then(|file| loader.source(file)) {
+        Some(file_source) => (file_source, Span::new(0, 1, 1, 0)),
+        None => (source, program.current_span),
+    };
+
+    let span = err.span.unwrap_or(fallback);
+    let code_at_line = source.lines().nth(span.line - 1).unwrap_or("").trim_start_matches('\u{feff}');
+
+    let error = ProgramError::new(err.message, span, code_at_line.to_string());
+    match &err.file {
+        Some(file) => error.in_file(file),
+        None => error,
+    }
+}
+
 pub fn tokenize(source: String) -> Result<(), errors::LangError> {
     let mut program = tokens::Program::new();
-    program.tokenize(&source);
+    program.tokenize(&source)?;
     println!("{}", program);
     Ok(())
 }
@@ -1,14 +1,45 @@
-use std::collections::HashMap;
-use crate::language::{binder::FunctionRegistry, datatypes::DataType, errors::LangError, expressions::Expression};
+use std::{cell::RefCell, collections::HashMap, path::{Path, PathBuf}, rc::Rc};
+use crate::language::{binder::FunctionRegistry, classes::ClassDef, datatypes::DataType, errors::LangError, expressions::Expression, span::Span};
 
 #[derive(Clone)]
 pub struct Scope {
     variables: HashMap<String, DataType>,
 }
 
+// where a function was defined
+#[derive(Clone)]
+pub struct Environment {
+    scopes: Vec<Rc<RefCell<Scope>>>, // shared with the defining code so captures are by reference
+    class: Option<Rc<ClassDef>>, // whose method it was defined in
+    file: Option<Rc<Path>>, // the imported file it was written in, None for the main one
+}
+
 pub struct ScopeStack {
-    scopes: Vec<Scope>,
+    scopes: Vec<Rc<RefCell<Scope>>>,
     registry: Option<*const FunctionRegistry>,
+    classes: Vec<Option<Rc<ClassDef>>>, // the class each running function belongs to, innermost last
+    imports: HashMap<PathBuf, Rc<Module>>, // files that already ran, by resolved path
+    file: Option<Rc<Path>>, // the imported file whose code is running, None for the main one
+}
+
+// a named scope that outlives its declaration, `Name::item` reads straight from it
+pub struct Module {
+    pub name: String,
+    pub scope: Rc<RefCell<Scope>>,
+    pub exports: Vec<String>, // the members marked `public`
+}
+
+impl Module {
+    pub fn get(&self, name: &str) -> Result<DataType, LangError> {
+        let value = self.scope.borrow().get(name);
+        match value {
+            Some(_) if !self.exports.iter().any(|export| export == name) => {
+                Err(LangError::new(format!("'{}' is private to module '{}'", name, self.name)))
+            },
+            Some(value) => Ok(value),
+            None => Err(LangError::new(format!("Module '{}' has no member '{}'", self.name, name))),
+        }
+    }
 }
 
 impl Scope {
@@ -17,18 +48,85 @@ impl Scope {
             variables: HashMap::new(),
         }
     }
+
+    pub fn get(&self, var_name: &str) -> Option<DataType> {
+        self.variables.get(var_name).cloned()
+    }
+
+    pub fn declare(&mut self, var_name: String, value: DataType) {
+        self.variables.insert(var_name, value);
+    }
+
+    pub fn entries(&self) -> Vec<(String, DataType)> {
+        self.variables.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+}
+
+impl Environment {
+    // a single empty scope in `file`, for code that must not see any of the caller's variables
+    pub fn empty(file: &Path) -> Self {
+        Environment {
+            scopes: vec![Rc::new(RefCell::new(Scope::new()))],
+            class: None,
+            file: Some(Rc::from(file)),
+        }
+    }
+
+    pub fn class(&self) -> Option<Rc<ClassDef>> {
+        self.class.clone()
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Module<{}>", self.name)
+    }
+}
+
+// environments can contain themselves through recursive functions, so they are compared and printed by identity
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        self.scopes.len() == other.scopes.len() && self.scopes.iter().zip(other.scopes.iter()).all(|(l, r)| Rc::ptr_eq(l, r))
+    }
+}
+
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Environment<{} scopes>", self.scopes.len())
+    }
 }
 
 impl ScopeStack {
     pub fn new() -> Self {
         ScopeStack {
-            scopes: vec![Scope::new()],
+            scopes: vec![Rc::new(RefCell::new(Scope::new()))],
             registry: None,
+            classes: Vec::new(),
+            imports: HashMap::new(),
+            file: None,
         }
     }
 
+    pub fn push_class(&mut self, class: Option<Rc<ClassDef>>) {
+        self.classes.push(class);
+    }
+
+    pub fn pop_class(&mut self) {
+        self.classes.pop();
+    }
+
+    pub fn current_class(&self) -> Option<Rc<ClassDef>> {
+        self.classes.last().cloned().flatten()
+    }
+
     pub fn push_scope(&mut self) {
-        self.scopes.push(Scope::new());
+        self.scopes.push(Rc::new(RefCell::new(Scope::new())));
     }
 
     pub fn pop_scope(&mut self) {
@@ -37,6 +135,53 @@ impl ScopeStack {
         }
     }
 
+    // pops the innermost scope and keeps it alive, functions declared in it still share it
+    pub fn pop_scope_shared(&mut self) -> Rc<RefCell<Scope>> {
+        self.scopes.pop().expect("scope stack is never empty")
+    }
+
+    pub fn imported(&self, path: &Path) -> Option<Rc<Module>> {
+        self.imports.get(path).cloned()
+    }
+
+    pub fn remember_import(&mut self, path: PathBuf, module: Rc<Module>) {
+        self.imports.insert(path, module);
+    }
+
+    pub fn capture(&self) -> Environment {
+        Environment {
+            scopes: self.scopes.clone(),
+            class: self.current_class(),
+            file: self.file.clone(),
+        }
+    }
+
+    // runs with the scopes of `env` instead of the caller's, hand the result back to `leave`
+    pub fn enter(&mut self, env: &Environment) -> Environment {
+        Environment {
+            scopes: std::mem::replace(&mut self.scopes, env.scopes.clone()),
+            class: None,
+            file: std::mem::replace(&mut self.file, env.file.clone()),
+        }
+    }
+
+    pub fn leave(&mut self, previous: Environment) {
+        self.scopes = previous.scopes;
+        self.file = previous.file;
+    }
+
+    // an error without a position happened in the statement at `span`, in the file that is running
+    pub fn locate(&self, err: LangError, span: Span) -> LangError {
+        if err.span.is_some() {
+            return err;
+        }
+
+        match &self.file {
+            Some(file) => err.or_at(span).in_file(file),
+            None => err.or_at(span),
+        }
+    }
+
     pub fn set_native_registry(&mut self, registry: &FunctionRegistry) {
         self.registry = Some(registry as *const FunctionRegistry);
     }
@@ -46,25 +191,23 @@ impl ScopeStack {
     }
 
     pub fn define_function(&mut self, fn_name: String, params: Vec<String>, body: Box<Expression>) {
-        self.scopes.last_mut().unwrap().variables.insert(fn_name, DataType::Function(params, (*body).clone()));
+        let function = DataType::Function(params, *body, self.capture());
+        self.declare(fn_name, function);
     }
 
     pub fn declare(&mut self, var_name: String, value: DataType) {
-        self.scopes.last_mut().unwrap().variables.insert(var_name, value);
+        self.scopes.last().unwrap().borrow_mut().variables.insert(var_name, value);
     }
-    
+
     pub fn set_or_declare(&mut self, var_name: String, value: DataType) {
-        if let Some(scope) = self.scopes.last_mut() {
-            if scope.variables.contains_key(&var_name) {
-                scope.variables.insert(var_name, value);
-            } else {
-                scope.variables.insert(var_name, value);
-            }
+        if let Some(scope) = self.scopes.last() {
+            scope.borrow_mut().variables.insert(var_name, value);
         }
     }
 
     pub fn set(&mut self, var_name: &str, value: DataType) -> Result<(), LangError> {
-        for scope in self.scopes.iter_mut().rev() {
+        for scope in self.scopes.iter().rev() {
+            let mut scope = scope.borrow_mut();
             if scope.variables.contains_key(var_name) {
                 scope.variables.insert(var_name.to_string(), value);
                 return Ok(());
@@ -73,12 +216,12 @@ impl ScopeStack {
         Err(LangError::new(format!("Variable '{}' is not declared", var_name)))
     }
 
-    pub fn get(&mut self, var_name: &str) -> Option<&DataType> {
+    pub fn get(&mut self, var_name: &str) -> Option<DataType> {
         for scope in self.scopes.iter().rev() {
-            if scope.variables.contains_key(var_name) {
-                return Some(scope.variables.get(var_name).unwrap());
+            if let Some(value) = scope.borrow().variables.get(var_name) {
+                return Some(value.clone());
             }
         }
         None
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,35 @@
+// offset and length are in bytes, line and column are 1-based (column counted in chars)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, line: usize, column: usize, length: usize) -> Self {
+        Span {
+            offset,
+            line,
+            column,
+            length,
+        }
+    }
+
+    // span starting at self and ending where `other` ends
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            offset: self.offset,
+            line: self.line,
+            column: self.column,
+            length: (other.offset + other.length).saturating_sub(self.offset),
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -0,0 +1,28 @@
+use std::rc::Rc;
+
+use crate::language::{datatypes::{DataType, EnumDef}, errors::LangError};
+
+// `Option.Some(value)`, `Option.None`, `Result.Ok(value)` and `Result.Err(error)`, declared for every program
+pub fn builtin_enums() -> Vec<Rc<EnumDef>> {
+    let option = EnumDef {
+        name: "Option".to_string(),
+        variants: vec![("Some".to_string(), vec!["value".to_string()]), ("None".to_string(), Vec::new())],
+    };
+    let result = EnumDef {
+        name: "Result".to_string(),
+        variants: vec![("Ok".to_string(), vec!["value".to_string()]), ("Err".to_string(), vec!["error".to_string()])],
+    };
+
+    vec![Rc::new(option), Rc::new(result)]
+}
+
+pub fn variant_name(args: &[DataType]) -> Result<DataType, LangError> {
+    if args.len() != 1 {
+        return Err(LangError::new("Invalid number of arguments for 'variant_name'".to_string()));
+    }
+
+    match &args[0] {
+        DataType::EnumValue(_, variant, _) => Ok(DataType::String(variant.clone())),
+        other => Err(LangError::new(format!("'variant_name' expects an enum value, got {}", other.type_name()))),
+    }
+}
@@ -0,0 +1,35 @@
+use crate::language::{datatypes::{map_key, DataType}, errors::LangError};
+
+fn expect_map<'a>(name: &str, args: &'a [DataType], arity: usize) -> Result<&'a Vec<(String, DataType)>, LangError> {
+    if args.len() != arity {
+        return Err(LangError::new(format!("Invalid number of arguments for '{}'", name)));
+    }
+
+    match &args[0] {
+        DataType::Map(entries) => Ok(entries),
+        other => Err(LangError::new(format!("'{}' expects a map, got {}", name, other.type_name()))),
+    }
+}
+
+pub fn keys(args: &[DataType]) -> Result<DataType, LangError> {
+    let entries = expect_map("keys", args, 1)?;
+    Ok(DataType::Array(entries.iter().map(|(k, _)| DataType::String(k.clone())).collect()))
+}
+
+pub fn values(args: &[DataType]) -> Result<DataType, LangError> {
+    let entries = expect_map("values", args, 1)?;
+    Ok(DataType::Array(entries.iter().map(|(_, v)| v.clone()).collect()))
+}
+
+pub fn has(args: &[DataType]) -> Result<DataType, LangError> {
+    let entries = expect_map("has", args, 2)?;
+    let key = map_key(&args[1])?;
+    Ok(DataType::Bool(entries.iter().any(|(k, _)| *k == key)))
+}
+
+// maps are values, so remove(m, key) returns a copy without `key`: `m = remove(m, "key")`
+pub fn remove(args: &[DataType]) -> Result<DataType, LangError> {
+    let entries = expect_map("remove", args, 2)?;
+    let key = map_key(&args[1])?;
+    Ok(DataType::Map(entries.iter().filter(|(k, _)| *k != key).cloned().collect()))
+}
@@ -2,6 +2,12 @@ pub mod math;
 pub mod string;
 pub mod logging;
 pub mod input;
+pub mod map;
+pub mod enums;
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::language::{binder::FunctionRegistry, datatypes::DataType, scopes::{Module, Scope}};
 
 pub fn register_std_functions(registry: &mut crate::language::binder::FunctionRegistry) {
     registry.register("len", string::str_len);
@@ -10,4 +16,20 @@ pub fn register_std_functions(registry: &mut crate::language::binder::FunctionRe
     registry.register("fib", math::fib);
     registry.register("rand", math::random);
     registry.register("input", input::std_listen);
+    registry.register("keys", map::keys);
+    registry.register("values", map::values);
+    registry.register("has", map::has);
+    registry.register("remove", map::remove);
+    registry.register("variant_name", enums::variant_name);
+}
+
+// every native is also reachable as `std::name`
+pub fn std_module(registry: &FunctionRegistry) -> DataType {
+    let mut scope = Scope::new();
+    for name in registry.names() {
+        scope.declare(name.clone(), DataType::NativeFunction(name.clone()));
+    }
+
+    let exports = registry.names().cloned().collect();
+    DataType::Module(Rc::new(Module { name: "std".to_string(), scope: Rc::new(RefCell::new(scope)), exports }))
 }
@@ -1,11 +1,20 @@
 use crate::language::{datatypes::DataType, errors::LangError};
 
+// len(s) and len(s, "bytes") count UTF-8 bytes, len(s, "chars") counts unicode scalar values
 pub fn str_len(args: &[DataType]) -> Result<DataType, LangError> {
-    if args.len() != 1 {
+    if args.is_empty() || args.len() > 2 {
         return Err(LangError::new("Invalid number of arguments for 'str_len'".to_string()));
     }
+
+    let count_chars = match args.get(1) {
+        None => false,
+        Some(DataType::String(mode)) if mode == "bytes" => false,
+        Some(DataType::String(mode)) if mode == "chars" => true,
+        Some(mode) => return Err(LangError::new(format!("Invalid mode '{}' for 'str_len', expected \"bytes\" or \"chars\"", mode))),
+    };
     
-    match args.get(0).unwrap() {
+    match &args[0] {
+        DataType::String(val) if count_chars => Ok(DataType::Float(val.chars().count() as f32)),
         DataType::String(val) => Ok(DataType::Float(val.len() as f32)),
         _ => Err(LangError::new("Invalid argument for 'str_len'".to_string())),
     }
@@ -1,14 +1,23 @@
+use unicode_ident::is_xid_continue;
+
+use crate::language::errors::LangError;
+use crate::language::span::Span;
 use crate::language::tokens::*;
 
-fn process_name_token(token_str: &str, cur_line: u32) -> Token {
+fn process_name_token(token_str: &str) -> Token {
     match token_str {
-        "let" | "local" => Token::LetToken(token_str.to_string(), cur_line),
+        "let" | "local" => Token::LetToken(token_str.to_string()),
         "if" => Token::IfToken(token_str.to_string()),
         "elseif" => Token::ElseIfToken(token_str.to_string()),
         "else" => Token::ElseToken(token_str.to_string()),
         "while" => Token::WhileToken(token_str.to_string()),
         "for" => Token::ForToken(token_str.to_string()),
         "function" => Token::FunctionToken(token_str.to_string()),
+        "enum" => Token::EnumToken(token_str.to_string()),
+        "class" => Token::ClassToken(token_str.to_string()),
+        "module" => Token::ModuleToken(token_str.to_string()),
+        "using" => Token::UsingToken(token_str.to_string()),
+        "import" => Token::ImportToken(token_str.to_string()),
         "public" => Token::PublicToken(token_str.to_string()),
         "private" => Token::PrivateToken(token_str.to_string()),
         "protected" => Token::ProtectedToken(token_str.to_string()),
@@ -21,13 +30,378 @@ fn process_name_token(token_str: &str, cur_line: u32) -> Token {
         "continue" => Token::ContinueToken(token_str.to_string()),
         "break" => Token::BreakToken(token_str.to_string()),
         "true" | "false" => Token::BoolToken(token_str.to_string()),
-        _ => Token::IdentifierToken(token_str.to_string(), cur_line),
+        "not" => Token::OperationToken(token_str.to_string()),
+        "and" => Token::AndToken(token_str.to_string()),
+        "or" => Token::OrToken(token_str.to_string()),
+        _ => Token::IdentifierToken(token_str.to_string()),
+    }
+}
+
+// span from the split token at `start` up to (not including) `end`
+fn span_between(tokens: &[SplitToken], start: usize, end: usize) -> Span {
+    let last = end.max(start + 1).min(tokens.len()) - 1;
+    tokens[start].span.to(&tokens[last].span)
+}
+
+// `--` is also a line comment, it only decrements when it directly follows an operand
+// and nothing but the end of the statement comes after it, as in `count--`
+fn is_decrement(tokens: &[SplitToken], start: usize, previous: Option<&SpannedToken>) -> bool {
+    if text_between(tokens, start, (start + 2).min(tokens.len())) != "--" {
+        return false;
+    }
+
+    let follows_operand = previous.is_some_and(|prev| {
+        matches!(prev.token, Token::IdentifierToken(_) | Token::ArrayEnd) && prev.span.offset + prev.span.length == tokens[start].span.offset
+    });
+    if !follows_operand {
+        return false;
+    }
+
+    let Some(rest) = (start + 2..tokens.len()).find(|idx| tokens[*idx].token_type != SplitTokenType::SplitToken) else {
+        return true;
+    };
+
+    let next = text_between(tokens, rest, (rest + 2).min(tokens.len()));
+    tokens[rest].token_type == SplitTokenType::NewlineToken
+        || next.starts_with(';')
+        || next.starts_with('}')
+        || matches!(next.as_str(), "//" | "/*" | "--")
+}
+
+// line comments (`//`, `--`) run to the end of the line, `///` is a doc comment and
+// `/* */` block comments nest. returns the index right after the comment
+fn scan_comment(tokens: &[SplitToken], start: usize) -> Result<Option<(usize, Option<SpannedToken>)>, LangError> {
+    let value_at = |idx: usize| tokens.get(idx).map(|t| t.value.as_str());
+
+    match (value_at(start), value_at(start + 1)) {
+        (Some("/"), Some("/")) | (Some("-"), Some("-")) => {
+            let mut end = start + 2;
+            while end < tokens.len() && tokens[end].token_type != SplitTokenType::NewlineToken {
+                end += 1;
+            }
+
+            let is_doc = value_at(start) == Some("/") && value_at(start + 2) == Some("/") && value_at(start + 3) != Some("/");
+            if !is_doc {
+                return Ok(Some((end, None)));
+            }
+
+            let text: String = tokens[start + 3..end].iter().map(|t| t.value.as_str()).collect();
+            let doc_token = SpannedToken {
+                token: Token::DocCommentToken(text.trim().to_string()),
+                span: span_between(tokens, start, end),
+            };
+
+            Ok(Some((end, Some(doc_token))))
+        },
+        (Some("/"), Some("*")) => {
+            let mut depth = 0;
+            let mut idx = start;
+
+            while idx < tokens.len() {
+                match (value_at(idx), value_at(idx + 1)) {
+                    (Some("/"), Some("*")) => {
+                        depth += 1;
+                        idx += 2;
+                    },
+                    (Some("*"), Some("/")) => {
+                        depth -= 1;
+                        idx += 2;
+
+                        if depth == 0 {
+                            return Ok(Some((idx, None)));
+                        }
+                    },
+                    _ => idx += 1,
+                }
+            }
+
+            Err(LangError::at("Unterminated block comment".to_string(), span_between(tokens, start, start + 2)))
+        },
+        _ => Ok(None),
     }
 }
 
-pub fn tokenize(new_tokens: &mut Vec<Token>, tokens: &Vec<SplitToken>) {
+// every operator longer than one char, longest first so that `..=` is not lexed as `..` followed by `=`
+// `//` already starts a comment, so floor division is spelled `~/`
+static MULTI_CHAR_OPERATORS: [&str; 22] = [
+    "..=",
+    "==", "!=", ">=", "<=", "&&", "||", "::", "->", "=>", "**", "..",
+    "+=", "-=", "*=", "/=", "%=", "<<", ">>", "~/", "++", "--",
+];
+
+fn char_at(tokens: &[SplitToken], idx: usize) -> Option<char> {
+    tokens.get(idx).and_then(|t| t.value.chars().next())
+}
+
+// decodes the escape sequence whose backslash sits at `start`, returns the char and the index after it
+fn scan_escape(tokens: &[SplitToken], start: usize) -> Result<(char, usize), LangError> {
+    let invalid = |end: usize, message: String| LangError::at(message, span_between(tokens, start, end));
+
+    let escaped = match char_at(tokens, start + 1) {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('u') => {
+            if char_at(tokens, start + 2) != Some('{') {
+                return Err(invalid(start + 3, "Expected '{' after '\\u' in unicode escape".to_string()));
+            }
+
+            let mut idx = start + 3;
+            let mut digits = String::new();
+            while let Some(digit) = char_at(tokens, idx) {
+                if digit == '}' {
+                    break;
+                }
+                if !digit.is_ascii_hexdigit() || digits.len() == 6 {
+                    return Err(invalid(idx + 1, format!("Invalid character '{}' in unicode escape", digit)));
+                }
+
+                digits.push(digit);
+                idx += 1;
+            }
+
+            if char_at(tokens, idx) != Some('}') || digits.is_empty() {
+                return Err(invalid(idx, "Malformed unicode escape, expected '\\u{XXXX}'".to_string()));
+            }
+
+            let code_point = u32::from_str_radix(&digits, 16).unwrap_or(u32::MAX);
+            return match char::from_u32(code_point) {
+                Some(ch) => Ok((ch, idx + 1)),
+                None => Err(invalid(idx + 1, format!("'\\u{{{}}}' is not a valid unicode code point", digits))),
+            };
+        },
+        Some(other) => return Err(invalid(start + 2, format!("Invalid escape sequence '\\{}'", other))),
+        None => return Err(invalid(start + 1, "Unterminated string literal".to_string())),
+    };
+
+    Ok((escaped, start + 2))
+}
+
+// finds the `}` closing the interpolation opened at `start`, skipping nested braces and strings
+fn scan_interpolation(tokens: &[SplitToken], start: usize, is_multiline: bool) -> Result<usize, LangError> {
+    let mut depth = 0;
+    let mut idx = start + 1;
+
+    loop {
+        match char_at(tokens, idx) {
+            None => break,
+            Some('\n') if !is_multiline => break,
+            Some('"') => idx = scan_string(tokens, idx)?.0,
+            Some('{') => {
+                depth += 1;
+                idx += 1;
+            },
+            Some('}') if depth == 0 => return Ok(idx),
+            Some('}') => {
+                depth -= 1;
+                idx += 1;
+            },
+            _ => idx += 1,
+        }
+    }
+
+    Err(LangError::at("Unterminated interpolation in string literal".to_string(), tokens[start].span))
+}
+
+// scans `"..."` (single line), `"""..."""` (multi-line) or `r"..."` (raw, no escapes) starting
+// at `start`. returns the index after the closing quote and the decoded contents, where every
+// `{expr}` becomes its own tokenized part and `{{`/`}}` stand for literal braces
+fn scan_string(tokens: &[SplitToken], start: usize) -> Result<(usize, Vec<StringPart>), LangError> {
+    let is_raw = char_at(tokens, start) == Some('r');
+    let quote_idx = if is_raw { start + 1 } else { start };
+    let is_triple = !is_raw && char_at(tokens, quote_idx + 1) == Some('"') && char_at(tokens, quote_idx + 2) == Some('"');
+
+    let body_start = if is_triple { quote_idx + 3 } else { quote_idx + 1 };
+    let unterminated = || LangError::at("Unterminated string literal".to_string(), span_between(tokens, start, body_start));
+
+    let mut idx = body_start;
+
+    // a line break right after the opening """ is not part of the string
+    if is_triple {
+        if char_at(tokens, idx) == Some('\r') && char_at(tokens, idx + 1) == Some('\n') {
+            idx += 2;
+        } else if char_at(tokens, idx) == Some('\n') {
+            idx += 1;
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut value = String::new();
+    loop {
+        let Some(ch) = char_at(tokens, idx) else {
+            return Err(unterminated());
+        };
+
+        match ch {
+            '"' if is_triple => {
+                if char_at(tokens, idx + 1) == Some('"') && char_at(tokens, idx + 2) == Some('"') {
+                    parts.push(StringPart::Text(value));
+                    return Ok((idx + 3, parts));
+                }
+
+                value.push(ch);
+                idx += 1;
+            },
+            '"' => {
+                parts.push(StringPart::Text(value));
+                return Ok((idx + 1, parts));
+            },
+            '{' | '}' if !is_raw && char_at(tokens, idx + 1) == Some(ch) => {
+                value.push(ch);
+                idx += 2;
+            },
+            '{' if !is_raw => {
+                let end = scan_interpolation(tokens, idx, is_triple)?;
+
+                let mut code = Vec::new();
+                tokenize(&mut code, &tokens[idx + 1..end])?;
+                if code.is_empty() {
+                    return Err(LangError::at("Empty interpolation in string literal".to_string(), span_between(tokens, idx, end + 1)));
+                }
+
+                parts.push(StringPart::Text(std::mem::take(&mut value)));
+                parts.push(StringPart::Code(code));
+                idx = end + 1;
+            },
+            '\\' if !is_raw => {
+                let (escaped, next_idx) = scan_escape(tokens, idx)?;
+                value.push(escaped);
+                idx = next_idx;
+            },
+            '\n' if !is_raw && !is_triple => return Err(unterminated()),
+            '\r' if char_at(tokens, idx + 1) == Some('\n') => idx += 1,
+            _ => {
+                value.push(ch);
+                idx += 1;
+            },
+        }
+    }
+}
+
+fn text_between(tokens: &[SplitToken], start: usize, end: usize) -> String {
+    tokens[start..end].iter().map(|t| t.value.as_str()).collect()
+}
+
+// `_` is only allowed between two digits
+fn validate_digits(digits: &str, radix: u32) -> Result<(), String> {
+    if digits.is_empty() {
+        return Err("expected digits".to_string());
+    }
+    if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err("'_' can only be used between digits".to_string());
+    }
+    if let Some(invalid) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+        return Err(format!("'{}' is not a valid base {} digit", invalid, radix));
+    }
+
+    Ok(())
+}
+
+// scans a numeric literal starting at `start`: decimals with an optional fraction and exponent
+// (`2.5E3`, `1e-9`) or 0x/0b/0o prefixed integers. returns the index after the literal and its
+// value written so that `f32::parse` accepts it
+fn scan_number(tokens: &[SplitToken], start: usize) -> Result<(usize, String), LangError> {
+    let is_word_char = |idx: usize| char_at(tokens, idx).is_some_and(|c| c == '_' || is_xid_continue(c));
+    let is_digit_or_separator = |idx: usize| char_at(tokens, idx).is_some_and(|c| c.is_ascii_digit() || c == '_');
+    let malformed = |end: usize, reason: String| LangError::at(
+        format!("Invalid numeric literal '{}': {}", text_between(tokens, start, end), reason),
+        span_between(tokens, start, end),
+    );
+
+    let radix = match (char_at(tokens, start), char_at(tokens, start + 1)) {
+        (Some('0'), Some('x' | 'X')) => 16,
+        (Some('0'), Some('b' | 'B')) => 2,
+        (Some('0'), Some('o' | 'O')) => 8,
+        _ => 10,
+    };
+
+    let mut end = start;
+    if radix != 10 {
+        end += 2;
+        while is_word_char(end) {
+            end += 1;
+        }
+
+        let digits = text_between(tokens, start + 2, end);
+        validate_digits(&digits, radix).map_err(|reason| malformed(end, reason))?;
+
+        return match u64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(value) => Ok((end, (value as f32).to_string())),
+            Err(_) => Err(malformed(end, "value does not fit in 64 bits".to_string())),
+        };
+    }
+
+    while is_digit_or_separator(end) {
+        end += 1;
+    }
+    let mut parts = vec![(start, end)];
+
+    // `1..3` and `1.method()` leave the dot alone, only `.<digit>` starts a fraction
+    if char_at(tokens, end) == Some('.') && char_at(tokens, end + 1).is_some_and(|c| c.is_ascii_digit()) {
+        let fraction_start = end + 1;
+        end = fraction_start;
+        while is_digit_or_separator(end) {
+            end += 1;
+        }
+        parts.push((fraction_start, end));
+    }
+
+    if matches!(char_at(tokens, end), Some('e' | 'E')) {
+        let mut exponent_start = end + 1;
+        if matches!(char_at(tokens, exponent_start), Some('+' | '-')) {
+            exponent_start += 1;
+        }
+
+        end = exponent_start;
+        while is_digit_or_separator(end) {
+            end += 1;
+        }
+        parts.push((exponent_start, end));
+    }
+
+    // anything glued to the literal makes it malformed, like `1.2.3` or `12abc`
+    if is_word_char(end) || char_at(tokens, end) == Some('.') && char_at(tokens, end + 1).is_some_and(|c| c.is_ascii_digit()) {
+        while is_word_char(end) || char_at(tokens, end) == Some('.') {
+            end += 1;
+        }
+
+        return Err(malformed(end, "unexpected characters after number".to_string()));
+    }
+
+    for (part_start, part_end) in parts {
+        validate_digits(&text_between(tokens, part_start, part_end), 10).map_err(|reason| malformed(end, reason))?;
+    }
+
+    Ok((end, text_between(tokens, start, end).replace('_', "")))
+}
+
+fn string_token(parts: Vec<StringPart>) -> Token {
+    if parts.iter().any(|part| matches!(part, StringPart::Code(_))) {
+        let parts = parts
+            .into_iter()
+            .filter(|part| !matches!(part, StringPart::Text(text) if text.is_empty()))
+            .collect();
+
+        return Token::InterpolatedStringToken(parts);
+    }
+
+    let text = parts
+        .into_iter()
+        .map(|part| match part {
+            StringPart::Text(text) => text,
+            StringPart::Code(_) => String::new(),
+        })
+        .collect();
+
+    Token::StringToken(text)
+}
+
+pub fn tokenize(new_tokens: &mut Vec<SpannedToken>, tokens: &[SplitToken]) -> Result<(), LangError> {
     let mut cur_idx = 0;
-    let mut cur_line = 1;
 
     while cur_idx < tokens.len() {
         let current_token = &tokens[cur_idx];
@@ -37,134 +411,211 @@ pub fn tokenize(new_tokens: &mut Vec<Token>, tokens: &Vec<SplitToken>) {
         }
 
         match current_token.token_type {
+            SplitTokenType::CharToken if current_token.value == "r" && char_at(tokens, cur_idx + 1) == Some('"') => {
+                let (next_token_idx, parts) = scan_string(tokens, cur_idx)?;
+                new_tokens.push(SpannedToken { token: string_token(parts), span: span_between(tokens, cur_idx, next_token_idx) });
+
+                cur_idx = next_token_idx;
+            },
             SplitTokenType::CharToken => {                
                 let mut base_str = String::new();
                 let mut next_token_idx = cur_idx;
                 
-                let mut is_fn_def = false;
                 while next_token_idx < tokens.len() {
                     let next_token: &SplitToken = &tokens[next_token_idx];
-                    
-                    if next_token.value == "(" {
-                        is_fn_def = true;
-                    }
-
-                    if !is_fn_def && (next_token.token_type == SplitTokenType::SplitToken 
-                            || next_token.token_type == SplitTokenType::EndExpressionToken
-                            || next_token.token_type == SplitTokenType::OperationToken) {
+                    if !matches!(next_token.token_type, SplitTokenType::CharToken | SplitTokenType::NumToken) {
                         break;
                     }
 
                     base_str.push_str(&next_token.value);
                     next_token_idx += 1;
-
-                    if is_fn_def && next_token.token_type == SplitTokenType::OperationToken
-                        && next_token.value == ")" { 
-                        break;
-                    }
                 }
                 
-                let current_token = process_name_token(&base_str, cur_line);
+                let current_token = process_name_token(&base_str);
 
-                new_tokens.push(current_token);
+                new_tokens.push(SpannedToken { token: current_token, span: span_between(tokens, cur_idx, next_token_idx) });
                 
                 cur_idx = next_token_idx;    
             },
             SplitTokenType::StrToken => {
-                let mut base_str = String::new();
-                let mut next_token_idx = cur_idx;
-                
-                while next_token_idx < tokens.len() {
-                    let next_token: &SplitToken = &tokens[next_token_idx];
-                    base_str.push_str(&next_token.value);
-                    next_token_idx+=1;
-
-                    if next_token.token_type == SplitTokenType::StrToken && next_token.value == "\"" && (next_token_idx-1) > cur_idx {
-                        break;
-                    }
-                }
-
-                let current_token = Token::StringToken(base_str);
-                new_tokens.push(current_token);
+                let (next_token_idx, parts) = scan_string(tokens, cur_idx)?;
+                new_tokens.push(SpannedToken { token: string_token(parts), span: span_between(tokens, cur_idx, next_token_idx) });
                 
                 cur_idx = next_token_idx; 
             },
             SplitTokenType::NumToken => {
-                let mut base_str = String::new();
-                let mut next_token_idx = cur_idx;
-                
-                while next_token_idx < tokens.len() {
-                    let next_token = &tokens[next_token_idx];
-                    if next_token.value == "_" {
-                        next_token_idx += 1;
-                        continue;
-                    }
-                    
-                    if next_token.value == "." {
-                        next_token_idx += 1;
-                        base_str.push_str(&next_token.value);
-                        
-                        continue;
-                    }
-                    
-                    if next_token.token_type != SplitTokenType::NumToken || Program::is_token_ending(next_token) {
-                        break;
-                    }
-                    base_str.push_str(&next_token.value);
-                    next_token_idx += 1;
-                }
-                
-                new_tokens.push(Token::NumericToken(base_str, cur_line));
+                let (next_token_idx, value) = scan_number(tokens, cur_idx)?;
+                new_tokens.push(SpannedToken { token: Token::NumericToken(value), span: span_between(tokens, cur_idx, next_token_idx) });
                 
                 cur_idx = next_token_idx; 
             },
             SplitTokenType::OperationToken | SplitTokenType::EndExpressionToken => {
-                let mut added_token = match current_token.value.as_str() {
+                if is_decrement(tokens, cur_idx, new_tokens.last()) {
+                    new_tokens.push(SpannedToken { token: Token::OperationToken("--".to_string()), span: span_between(tokens, cur_idx, cur_idx + 2) });
+                    cur_idx += 2;
+                    continue;
+                }
+
+                if let Some((next_idx, doc_token)) = scan_comment(tokens, cur_idx)? {
+                    new_tokens.extend(doc_token);
+                    cur_idx = next_idx;
+                    continue;
+                }
+
+                // maximal munch, the longest operator starting here wins
+                let op_len = MULTI_CHAR_OPERATORS
+                    .iter()
+                    .find(|op| {
+                        let len = op.chars().count();
+                        cur_idx + len <= tokens.len() && text_between(tokens, cur_idx, cur_idx + len) == **op
+                    })
+                    .map(|op| op.chars().count())
+                    .unwrap_or(1);
+
+                let op = text_between(tokens, cur_idx, cur_idx + op_len);
+                let added_token = match op.as_str() {
                     "{" => Token::ScopeBeginToken,
                     "}" => Token::ScopeEndToken,
                     "[" => Token::ArrayBegin,
                     "]" => Token::ArrayEnd,
-                    "(" => Token::OpenParenthesisToken(current_token.value.clone()),
-                    ")" => Token::CloseParenthesisToken(current_token.value.clone()),
-                    ";" => Token::EndExpressionToken(current_token.value.clone()),
-                    _ => Token::OperationToken(current_token.value.clone(), cur_line),
+                    "(" => Token::OpenParenthesisToken(op),
+                    ")" => Token::CloseParenthesisToken(op),
+                    ";" => Token::EndExpressionToken(op),
+                    "==" => Token::CompareToken(op),
+                    "!=" => Token::NotEqualToken(op),
+                    ">=" => Token::GreaterEqualToken(op),
+                    "<=" => Token::LessEqualToken(op),
+                    "&&" => Token::AndToken(op),
+                    "||" => Token::OrToken(op),
+                    "::" => Token::NamespaceAccessToken(op),
+                    _ => Token::OperationToken(op),
                 };
 
-                let mut base_str = String::new();
-                base_str.push_str(&current_token.value);
-
-                if cur_idx + 1 < tokens.len() {
-                    let next_token = &tokens[cur_idx + 1];
-
-                    if next_token.token_type == SplitTokenType::OperationToken && ( next_token.value == "=" || current_token.value == "=" ) {
-                        base_str.push_str(&next_token.value);
-                        cur_idx += 1;
-
-                        added_token = match base_str.as_str() {
-                            "==" => Token::CompareToken(base_str),
-                            "!=" => Token::NotEqualToken(base_str),
-                            ">=" => Token::GreaterEqualToken(base_str),
-                            "<=" => Token::LessEqualToken(base_str),
-                            _ => added_token,
-                        }
-                    }
-                }
-
-                new_tokens.push(added_token);
+                new_tokens.push(SpannedToken { token: added_token, span: span_between(tokens, cur_idx, cur_idx + op_len) });
 
-                cur_idx += 1;
+                cur_idx += op_len;
             }
             _ => {
-                if current_token.value == "\r" {
-                    cur_line += 1;
-                }
-                
                 cur_idx += 1;
             }
         }
     }
+
+    Ok(())
 }
 
 pub fn is_then_token(token: &Token) -> bool {
     matches!(token, Token::ThenToken | Token::ScopeBeginToken)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Result<Vec<Token>, LangError> {
+        let mut program = Program::new();
+        program.tokenize(source)?;
+
+        let mut tokens = Vec::new();
+        loop {
+            match program.next() {
+                Token::EofToken => return Ok(tokens),
+                token => tokens.push(token),
+            }
+        }
+    }
+
+    fn lex_one(source: &str) -> Token {
+        let tokens = lex(source).unwrap();
+        assert_eq!(tokens.len(), 1, "{:?}", tokens);
+        tokens.into_iter().next().unwrap()
+    }
+
+    fn number(value: &str) -> Token {
+        Token::NumericToken(value.to_string())
+    }
+
+    fn string(value: &str) -> Token {
+        Token::StringToken(value.to_string())
+    }
+
+    fn lex_error(source: &str) -> String {
+        lex(source).unwrap_err().message
+    }
+
+    #[test]
+    fn decimal_literals() {
+        assert_eq!(lex_one("42"), number("42"));
+        assert_eq!(lex_one("3.25"), number("3.25"));
+        assert_eq!(lex_one("1_000_000"), number("1000000"));
+        assert_eq!(lex_one("2.5E3"), number("2.5E3"));
+        assert_eq!(lex_one("1e-9"), number("1e-9"));
+    }
+
+    #[test]
+    fn prefixed_integer_literals() {
+        assert_eq!(lex_one("0xff"), number("255"));
+        assert_eq!(lex_one("0XFF_FF"), number("65535"));
+        assert_eq!(lex_one("0b1010"), number("10"));
+        assert_eq!(lex_one("0o17"), number("15"));
+    }
+
+    #[test]
+    fn dots_after_numbers_are_left_alone() {
+        let tokens = lex("1..3").unwrap();
+        assert_eq!(tokens.first(), Some(&number("1")));
+        assert_eq!(tokens.last(), Some(&number("3")));
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        assert!(lex_error("1__0").contains("'_' can only be used between digits"));
+        assert!(lex_error("100_").contains("'_' can only be used between digits"));
+        assert!(lex_error("0b102").contains("'2' is not a valid base 2 digit"));
+        assert!(lex_error("0x").contains("expected digits"));
+        assert!(lex_error("1.2.3").contains("unexpected characters after number"));
+        assert!(lex_error("12abc").contains("unexpected characters after number"));
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(lex_one(r#""a\tb\n""#), string("a\tb\n"));
+        assert_eq!(lex_one(r#""say \"hi\"""#), string("say \"hi\""));
+        assert_eq!(lex_one(r#""\u{1F600}""#), string("\u{1F600}"));
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        assert!(lex_error(r#""\q""#).contains("Invalid escape sequence '\\q'"));
+        assert!(lex_error(r#""\u{110000}""#).contains("is not a valid unicode code point"));
+        assert!(lex_error(r#""\u1234""#).contains("Expected '{' after '\\u'"));
+    }
+
+    #[test]
+    fn raw_and_multiline_strings() {
+        assert_eq!(lex_one(r#"r"C:\path\{x}""#), string(r"C:\path\{x}"));
+        assert_eq!(lex_one("\"\"\"\nline one\n\"quoted\"\n\"\"\""), string("line one\n\"quoted\"\n"));
+        assert_eq!(lex_one("\"\"\"\r\na\r\nb\"\"\""), string("a\nb"));
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        assert!(lex_error("\"open").contains("Unterminated string literal"));
+        assert!(lex_error("\"line\nbreak\"").contains("Unterminated string literal"));
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        let Token::InterpolatedStringToken(parts) = lex_one(r#""sum: {a + 1}!""#) else {
+            panic!("expected an interpolated string");
+        };
+
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], StringPart::Text("sum: ".to_string()));
+        assert!(matches!(&parts[1], StringPart::Code(code) if code.len() == 3));
+        assert_eq!(parts[2], StringPart::Text("!".to_string()));
+
+        assert_eq!(lex_one(r#""{{braces}}""#), string("{braces}"));
+        assert!(lex_error(r#""{}""#).contains("Empty interpolation"));
+    }
+}
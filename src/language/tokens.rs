@@ -1,7 +1,18 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use crate::files::file;
 use crate::language::binder::FunctionRegistry;
+use crate::language::checker::Checker;
+use crate::language::datatypes::{DataType, DataTypeType};
 use crate::language::errors::{LangError};
 use crate::language::expressions::*;
+use crate::language::loader::ModuleLoader;
 use crate::language::scopes::ScopeStack;
+use crate::language::span::Span;
 use crate::language::stdlib;
 use crate::language::tokenizer::is_then_token;
 
@@ -22,12 +33,12 @@ pub enum SplitTokenType {
 #[derive(Clone, PartialEq, Debug)]
 pub enum Token {
     // Arithmetic
-    OperationToken(String, u32),
+    OperationToken(String),
 
     // Program creation
-    LetToken(String, u32), // variable declaration
+    LetToken(String), // variable declaration
     MutableToken(String), // make variable mutable
-    IdentifierToken(String, u32), // variable name
+    IdentifierToken(String), // variable name
     ScopeBeginToken, // {
     ScopeEndToken, // }
     OpenParenthesisToken(String), // (
@@ -36,8 +47,9 @@ pub enum Token {
 
     // Types
     BoolToken(String),
-    NumericToken(String, u32),
+    NumericToken(String),
     StringToken(String),
+    InterpolatedStringToken(Vec<StringPart>), // "text {expr} text"
     ArrayBegin,
     ArrayEnd,
 
@@ -68,6 +80,11 @@ pub enum Token {
 
     // Functions
     FunctionToken(String),
+    EnumToken(String),
+    ClassToken(String),
+    ModuleToken(String),
+    UsingToken(String),
+    ImportToken(String),
     PublicToken(String),
     PrivateToken(String),
     ProtectedToken(String),
@@ -77,36 +94,59 @@ pub enum Token {
     FinishLine(String),
     SplitToken(String),
     
+    // trivia
+    DocCommentToken(String), // ///
+
     // other
     EofToken,
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<SpannedToken>),
+}
+
 #[derive(Clone)]
 pub struct SplitToken {
     pub token_type: SplitTokenType,
     pub value: String,
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
 pub struct Program {
-    pub current_line: usize,
+    pub current_span: Span,
     pub source: String,
-    pub tokens: Vec<Token>,
+    pub tokens: Vec<SpannedToken>,
+    pub trivia: Vec<SpannedToken>,
+    pub diagnostics: Vec<LangError>,
     pub scopes: ScopeStack,
     pub registry: FunctionRegistry,
+    pub path: Option<PathBuf>, // the file being parsed, imports resolve relative to it
+    pub loader: Rc<RefCell<ModuleLoader>>,
+    expression_depth: usize, // 1 while parsing the outermost expression of a statement
+    in_header: bool, // parsing an if/while/for header, where `Name {` opens the body
+    previous: Option<SpannedToken>, // the token `next` returned last
 }
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::OperationToken(val, _) => write!(f, "\x1b[0;31mToken<\x1b[1;31mOperator, \'{}\'\x1b[0;31m>\x1b[0m", val),
-            Token::LetToken(val, _) => write!(f, "\x1b[0;32mToken\x1b[1;32m<Decl, {}>\x1b[0m", val),
-            Token::IdentifierToken(val, _) => write!(f, "\x1b[0;33mToken\x1b[1;33m<Identifier, \"{}\">\x1b[0m", val),
-            Token::NumericToken(val, _) => write!(f, "\x1b[0;33mToken\x1b[1;33m<Number, {}>\x1b[0m", val),
+            Token::OperationToken(val) => write!(f, "\x1b[0;31mToken<\x1b[1;31mOperator, \'{}\'\x1b[0;31m>\x1b[0m", val),
+            Token::LetToken(val) => write!(f, "\x1b[0;32mToken\x1b[1;32m<Decl, {}>\x1b[0m", val),
+            Token::IdentifierToken(val) => write!(f, "\x1b[0;33mToken\x1b[1;33m<Identifier, \"{}\">\x1b[0m", val),
+            Token::NumericToken(val) => write!(f, "\x1b[0;33mToken\x1b[1;33m<Number, {}>\x1b[0m", val),
             Token::EndExpressionToken(val) => write!(f, "\x1b[0;34mToken\x1b[1;34m<Next, {}>\x1b[0m", val),
             Token::ScopeBeginToken => write!(f, "\x1b[0;35mToken\x1b[1;35m<ScopeBegin, {}>\x1b[0m", "{"),
             Token::ScopeEndToken => write!(f, "\x1b[0;35mToken\x1b[1;35m<ScopeEnd, {}>\x1b[0m", "}"),
             Token::FunctionToken(val) => write!(f, "\x1b[0;31mToken\x1b[1;31m<DeclFunction, {}>\x1b[0m", val),
-            Token::StringToken(val) => write!(f, "\x1b[0;32mToken\x1b[1;32m<String, {}>\x1b[0m", val),
+            Token::StringToken(val) => write!(f, "\x1b[0;32mToken\x1b[1;32m<String, {:?}>\x1b[0m", val),
             Token::ReturnToken(val) => write!(f, "\x1b[0;31mToken\x1b[1;31m<ReturnVal, {}>\x1b[0m", val),
 
 
@@ -117,118 +157,74 @@ impl std::fmt::Display for Token {
 
 impl std::fmt::Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for token in self.tokens.iter().rev() {
-            write!(f, "{}\n", token)?;
+        for spanned in self.tokens.iter().rev() {
+            writeln!(f, "{}\t{}", spanned.span, spanned.token)?;
         }
         Ok(())
     }
 }
 
 
-pub fn operator_binding_power(token: &str) -> (f32, f32) {
-    match token {
-        "=" => (0.1, 0.2),
+pub fn operator_binding_power(token: &str) -> Option<(f32, f32)> {
+    let binding_power = match token {
+        "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "..=" => (0.1, 0.2),
         "||" => (0.3, 0.4),
         "&&" => (0.5, 0.6),
         "==" | "!=" => (0.7, 0.8),
-        "<" | ">" | "<=" | ">=" => (0.9, 1.0),
+        "<" | ">" | "<=" | ">=" => (0.9, 0.91),
+        "|" => (0.92, 0.93),
+        "^" => (0.94, 0.95),
+        "&" => (0.96, 0.97),
+        "<<" | ">>" => (0.98, 0.99),
+        ".." => (0.995, 0.996), // as in Lua, above comparisons so `a .. b == c` compares the joined string
         "+" | "-" => (1.0, 1.1),
-        "*" | "/" => (2.0, 2.1),
-        "." | "[" => (4.0, 4.1),
-        _ => panic!("Invalid operator: {}", token),
-    }
+        "*" | "/" | "~/" | "%" => (2.0, 2.1),
+        "**" => (3.1, 3.0), // right associative
+        "." | "[" | "(" | "++" | "--" => (4.0, 4.1),
+        "::" => (5.0, 5.1),
+        _ => return None,
+    };
+
+    Some(binding_power)
 }
 
-fn parse_function_signature(signature: &str) -> Result<(String, Vec<String>), LangError> {
-    // Parse "name(param1,param2)" into ("name", ["param1", "param2"])
-    if let Some(paren_pos) = signature.find('(') {
-        let fn_name = signature[..paren_pos].to_string();
-        let params_str = &signature[paren_pos+1..signature.len()-1]; // Remove ( and )
-        
-        let params: Vec<String> = if params_str.is_empty() {
-            vec![]
-        } else {
-            params_str.split(',')
-                .map(|s| s.trim().to_string())
-                .collect()
-        };
-        
-        Ok((fn_name, params))
-    } else {
-        Err(LangError::new(format!("Invalid function signature: {}", signature)))
+// the arithmetic behind a compound assignment or increment
+fn compound_operator(token: &str) -> Option<&'static str> {
+    match token {
+        "+=" | "++" => Some("+"),
+        "-=" | "--" => Some("-"),
+        "*=" => Some("*"),
+        "/=" => Some("/"),
+        "%=" => Some("%"),
+        "..=" => Some(".."),
+        _ => None,
     }
 }
 
-fn is_function_call(identifier: &str) -> bool {
-    identifier.contains('(') && identifier.ends_with(')')
+pub fn prefix_binding_power(token: &str) -> Option<f32> {
+    match token {
+        // below `**` so that -2 ** 2 is -(2 ** 2)
+        "-" | "!" | "not" | "~" => Some(2.5),
+        _ => None,
+    }
 }
 
-fn parse_function_call_with_program(call_str: &str) -> Result<(String, Vec<Expression>), LangError> {
-    if let Some(paren_pos) = call_str.find('(') {
-        let fn_name = call_str[..paren_pos].to_string();
-        let args_str = &call_str[paren_pos+1..call_str.len()-1]; // Remove ( and )
-        
-        if args_str.is_empty() {
-            return Ok((fn_name, vec![]));
-        }
-        
-        let arg_strings = split_arguments(args_str);
-        
-        let mut arg_expressions = Vec::new();
-        for arg_str in arg_strings {
-            let mut mini_program = Program::new();
-            mini_program.tokenize(&arg_str.trim().to_string());
-            let expr = mini_program.parse_expression(0.0)?;
-            arg_expressions.push(expr);
-        }
-        
-        Ok((fn_name, arg_expressions))
-    } else {
-        Err(LangError::new(format!("Invalid function call: {}", call_str)))
+fn visibility_of(token: &Token) -> Visibility {
+    match token {
+        Token::PublicToken(_) => Visibility::Public,
+        Token::ProtectedToken(_) => Visibility::Protected,
+        _ => Visibility::Private,
     }
 }
 
-fn split_arguments(args_str: &str) -> Vec<String> {
-    let mut args = Vec::new();
-    let mut current_arg = String::new();
-    let mut paren_depth = 0;
-    let mut in_string = false;
-    
-    for ch in args_str.chars() {
-        match ch {
-            '"' => {
-                in_string = !in_string;
-                current_arg.push(ch);
-            }
-            '(' if !in_string => {
-                paren_depth += 1;
-                current_arg.push(ch);
-            }
-            ')' if !in_string => {
-                paren_depth -= 1;
-                current_arg.push(ch);
-            }
-            ',' if !in_string && paren_depth == 0 => {
-                args.push(current_arg.trim().to_string());
-                current_arg.clear();
-            }
-            _ => {
-                current_arg.push(ch);
-            }
-        }
-    }
-    
-    if !current_arg.is_empty() {
-        args.push(current_arg.trim().to_string());
-    }
-    
-    args
+fn is_comma(token: &Token) -> bool {
+    matches!(token, Token::OperationToken(op) if op == ",")
 }
 
 
 impl Token {
     pub fn is_declaration(&self) -> bool {
-        matches!(self, Token::LetToken(_, _))
+        matches!(self, Token::LetToken(_))
     }
 
     pub fn is_expression_end(&self) -> bool {
@@ -239,69 +235,120 @@ impl Token {
 impl Program {
     pub fn new() -> Self {
         Program {
-            current_line: 1,
+            current_span: Span::new(0, 1, 1, 0),
             source: "".to_string(),
             tokens: vec![],
+            trivia: vec![],
+            diagnostics: vec![],
             scopes: ScopeStack::new(),
             registry: FunctionRegistry::new(),
+            path: None,
+            loader: Rc::new(RefCell::new(ModuleLoader::new(Vec::new()))),
+            expression_depth: 0,
+            in_header: false,
+            previous: None,
         }
     }
 
-    pub fn tokenize(&mut self, source: &String) {
-        let tokens: Vec<SplitToken>  =  source
-            .chars()
-            .map(|token_char| match token_char {
-                '0'..='9' => SplitToken {
-                    token_type: SplitTokenType::NumToken,
-                    value: token_char.to_string(),
-                },
-                'a'..='z' | 'A'..='Z' | '_' | ',' => SplitToken {
-                    token_type: SplitTokenType::CharToken,
-                    value: token_char.to_string(),
-                },
-                ' ' | '\t' | '\n' | '\r' => SplitToken {
-                    token_type: SplitTokenType::SplitToken,
-                    value: token_char.to_string(),
-                },
-                '\"' => SplitToken {
-                    token_type: SplitTokenType::StrToken,
-                    value: token_char.to_string(),
-                },
-                ';' => SplitToken {
-                    token_type: SplitTokenType::EndExpressionToken,
-                    value: token_char.to_string(),
-                },
-                _ => SplitToken {
-                    token_type: SplitTokenType::OperationToken,
-                    value: token_char.to_string(),
-                },
-            }).collect();
+    pub fn tokenize(&mut self, source: &str) -> Result<(), LangError> {
+        let mut line = 1;
+        let mut column = 1;
+        let mut tokens: Vec<SplitToken> = Vec::new();
+        let mut chars = source.char_indices();
+        let mut in_word = false;
+
+        // a leading BOM is not part of the program, but offsets still point into the raw source
+        if source.starts_with('\u{feff}') {
+            chars.next();
+        }
 
-        self.process_tokens(tokens);
+        for (offset, token_char) in chars {
+            let token_type = match token_char {
+                '0'..='9' => SplitTokenType::NumToken,
+                'a'..='z' | 'A'..='Z' | '_' => SplitTokenType::CharToken,
+                c if is_xid_start(c) => SplitTokenType::CharToken,
+                // combining marks and other XID_Continue characters only extend an identifier
+                c if in_word && is_xid_continue(c) => SplitTokenType::CharToken,
+                '\n' => SplitTokenType::NewlineToken,
+                ' ' | '\t' | '\r' => SplitTokenType::SplitToken,
+                '\"' => SplitTokenType::StrToken,
+                ';' => SplitTokenType::EndExpressionToken,
+                _ => SplitTokenType::OperationToken,
+            };
+
+            in_word = matches!(token_type, SplitTokenType::CharToken | SplitTokenType::NumToken);
+            tokens.push(SplitToken {
+                token_type,
+                value: token_char.to_string(),
+                span: Span::new(offset, line, column, token_char.len_utf8()),
+            });
+
+            // CRLF counts as a single line break, the '\r' is plain whitespace
+            if token_char == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+
+        self.process_tokens(tokens)
     }
 
-    fn process_tokens(&mut self, tokens: Vec<SplitToken>) {
-        let mut new_tokens: Vec<Token> = Vec::new();
+    fn process_tokens(&mut self, tokens: Vec<SplitToken>) -> Result<(), LangError> {
+        let mut new_tokens: Vec<SpannedToken> = Vec::new();
         
         use crate::language::tokenizer::tokenize;
-        tokenize(&mut new_tokens, &tokens);
+        tokenize(&mut new_tokens, &tokens)?;
+
+        // doc comments are kept aside for tooling, the parser never sees them
+        let (trivia, mut new_tokens): (Vec<SpannedToken>, Vec<SpannedToken>) = new_tokens
+            .into_iter()
+            .partition(|spanned| matches!(spanned.token, Token::DocCommentToken(_)));
         
         new_tokens.reverse();
 
         self.tokens = new_tokens;
+        self.trivia = trivia;
+        Ok(())
     }
     
     pub fn next(&mut self) -> Token {
-        self.tokens.pop().unwrap_or(Token::EofToken)
+        match self.tokens.pop() {
+            Some(spanned) => {
+                self.current_span = spanned.span;
+                self.previous = Some(spanned.clone());
+                spanned.token
+            },
+            None => Token::EofToken,
+        }
     }
 
     pub fn peek(&mut self) -> Token {
-        self.tokens.last().cloned().unwrap_or(Token::EofToken)
+        self.tokens.last().map(|spanned| spanned.token.clone()).unwrap_or(Token::EofToken)
     }
 
-    pub fn begin(&mut self) -> Result<(), LangError> {
-        stdlib::register_std_functions(&mut self.registry);
-        self.scopes.set_native_registry(&self.registry);
+    // `n` tokens past the next one, peek_nth(0) is peek()
+    pub fn peek_nth(&self, n: usize) -> Token {
+        self.tokens
+            .len()
+            .checked_sub(n + 1)
+            .map(|idx| self.tokens[idx].token.clone())
+            .unwrap_or(Token::EofToken)
+    }
+
+    pub fn peek_span(&self) -> Span {
+        self.tokens.last().map(|spanned| spanned.span).unwrap_or(self.current_span)
+    }
+
+    pub fn error(&self, message: String) -> LangError {
+        LangError::at(message, self.current_span)
+    }
+
+    // parses the whole program, syntax errors are collected in `diagnostics` and parsing
+    // picks up again at the next statement
+    pub fn parse(&mut self) -> Vec<(Expression, Span)> {
+        let mut statements = Vec::new();
 
         loop {
             let next_token = self.peek();
@@ -312,189 +359,842 @@ impl Program {
                 continue;
             }
 
-            match self.parse_expression(0.0) {
-                Ok(expr) => {
-                    if let Expression::FunctionDeclaration(fn_name, params, body) = expr {
-                        self.scopes.define_function(fn_name, params, body);
-                    } else if let Some((var_name, expr_tree, is_declaration)) = expr.is_assign() {
-                        let wrapped = expr_tree.eval(&mut self.scopes);
-                        
-                        match wrapped {
-                            Ok(value) => {
-                                if is_declaration {
-                                    self.scopes.declare(var_name, value);
-                                } else if let Err(err) = self.scopes.set(&var_name, value) {
-                                    return Err(err);
-                                }
-                            },
-
-                            Err(err) => return Err(err)
-                        }
-                    } else {
-                        match expr.eval(&mut self.scopes) {
-                            Ok(value) => {
-                                if expr.evaluate_print() {
-                                    println!("{}", value)
-                                }
-                            },
-                            Err(err) => return Err(err),
-                        };
-                    };
+            let statement_start = self.peek_span();
+            match self.parse_statement() {
+                Ok(expr) => statements.push((expr, statement_start.to(&self.current_span))),
+                Err(err) => {
+                    self.diagnostics.push(err);
+                    self.synchronize(false);
+                }
+            }
+        }
+
+        statements
+    }
+
+    // skips the rest of a broken statement: past the next `;`, or up to the `}`/`end` that
+    // closes the block we are in
+    fn synchronize(&mut self, in_block: bool) {
+        // the error was raised on the terminator itself: a `;` already ended the statement
+        // and a `}`/`end` still has to close the block
+        match self.previous.take() {
+            Some(SpannedToken { token: Token::EndExpressionToken(_), .. }) => return,
+            Some(spanned @ SpannedToken { token: Token::ScopeEndToken, .. }) => {
+                if in_block {
+                    self.tokens.push(spanned);
+                }
+                return;
+            },
+            _ => {},
+        }
+
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                Token::EofToken => break,
+                Token::EndExpressionToken(_) if depth == 0 => {
+                    self.next();
+                    break;
                 },
-                Err(err) => return Err(err),
+                Token::ScopeEndToken if depth == 0 => break,
+                Token::ScopeEndToken => depth -= 1,
+                Token::ScopeBeginToken => depth += 1,
+                _ => {},
+            }
+
+            self.next();
+        }
+    }
+
+    fn parse_program(&mut self) -> Result<Vec<(Expression, Span)>, Vec<LangError>> {
+        stdlib::register_std_functions(&mut self.registry);
+
+        let statements = self.parse();
+        if !self.diagnostics.is_empty() {
+            return Err(std::mem::take(&mut self.diagnostics));
+        }
+
+        Ok(statements)
+    }
+
+    // parses and type checks the program without running it, runs only report syntax errors up front
+    pub fn check(&mut self) -> Result<(), Vec<LangError>> {
+        let statements = self.parse_program()?;
+        let diagnostics = Checker::check(&statements, &self.registry);
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        Ok(())
+    }
+
+    pub fn begin(&mut self) -> Result<(), Vec<LangError>> {
+        let statements = self.parse_program()?;
+        self.scopes.set_native_registry(&self.registry);
+        self.scopes.declare("std".to_string(), stdlib::std_module(&self.registry));
+        for def in stdlib::enums::builtin_enums() {
+            self.scopes.declare(def.name.clone(), DataType::Enum(def));
+        }
+
+        for (expr, statement_span) in statements {
+            // runtime errors carry no position of their own, point them at the statement
+            if let Err(err) = self.run_statement(expr) {
+                return Err(vec![err.or_at(statement_span)]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_statement(&mut self, expr: Expression) -> Result<(), LangError> {
+        // visibility only matters to code outside a module, here it is an ordinary declaration
+        let expr = match expr {
+            Expression::Modified(_, declaration) => *declaration,
+            expr => expr,
+        };
+
+        if let Expression::FunctionDeclaration(fn_name, params, body, _) = expr {
+            self.scopes.define_function(fn_name, params, body);
+        } else if let Some((var_name, expr_tree, is_declaration)) = expr.is_assign() {
+            let value = expr_tree.eval(&mut self.scopes)?;
+
+            if is_declaration {
+                self.scopes.declare(var_name, value);
+            } else {
+                self.scopes.set(&var_name, value)?;
+            }
+        } else {
+            let value = expr.eval(&mut self.scopes)?;
+            if expr.evaluate_print() {
+                println!("{}", value)
             }
         }
 
         Ok(())
     }
 
+    // `import "./util.nx"` or `import util.strings`, the file is parsed right away
+    fn parse_import(&mut self) -> Result<Expression, LangError> {
+        let start = self.current_span;
+        let (spec, is_path) = match self.next() {
+            Token::StringToken(path) => (path, true),
+            Token::IdentifierToken(first) => {
+                let mut spec = first;
+                while matches!(self.peek(), Token::OperationToken(op) if op == ".") {
+                    self.next();
+                    match self.next() {
+                        Token::IdentifierToken(part) => spec = format!("{}.{}", spec, part),
+                        t => return Err(self.error(format!("Expected module name after '.', got: {:?}", t))),
+                    }
+                }
+                (spec, false)
+            },
+            t => return Err(self.error(format!("Expected a file path or module name after 'import', got: {:?}", t))),
+        };
+
+        let span = start.to(&self.current_span);
+        let resolved = self.loader.borrow().resolve(&spec, is_path, self.path.as_deref());
+        let path = resolved.map_err(|msg| LangError::at(msg, span))?;
+        let body = match self.load_module(&path) {
+            Ok(body) => body,
+            // every error in the imported file is reported, the import itself fails with the last one
+            Err(errors) => {
+                let mut errors: Vec<LangError> = errors.into_iter().map(|err| err.or_at(span)).collect();
+                let last = errors.pop().unwrap_or_else(|| LangError::at(format!("Cannot import '{}'", spec), span));
+                self.diagnostics.extend(errors);
+                return Err(last);
+            },
+        };
+
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or(spec);
+        Ok(Expression::Import(name, path, body))
+    }
+
+    fn load_module(&mut self, path: &Path) -> Result<Rc<Vec<(Expression, Span)>>, Vec<LangError>> {
+        let cached = self.loader.borrow().cached(path);
+        if let Some(body) = cached {
+            return Ok(body);
+        }
+
+        self.loader.borrow_mut().enter(path.to_path_buf()).map_err(|msg| vec![LangError::new(msg)])?;
+        let result = self.parse_file(path);
+        self.loader.borrow_mut().leave(path, result.as_ref().ok().cloned());
+
+        result
+    }
+
+    // errors inside the imported file are tagged with it, so they are rendered against its own source
+    fn parse_file(&self, path: &Path) -> Result<Rc<Vec<(Expression, Span)>>, Vec<LangError>> {
+        let source = file::validate_and_read_file(&path.to_string_lossy()).map_err(|msg| vec![LangError::new(msg)])?;
+        self.loader.borrow_mut().add_source(path, source.clone());
+
+        let mut program = Program::new();
+        program.path = Some(path.to_path_buf());
+        program.loader = self.loader.clone();
+
+        if let Err(err) = program.tokenize(&source) {
+            let fallback = program.current_span;
+            return Err(vec![err.or_at(fallback).in_file(path)]);
+        }
+
+        let statements = program.parse();
+        if !program.diagnostics.is_empty() {
+            let fallback = program.current_span;
+            return Err(program.diagnostics.drain(..).map(|err| err.or_at(fallback).in_file(path)).collect());
+        }
+
+        Ok(Rc::new(statements))
+    }
+
+    // `enum Name { Unit, WithPayload(a, b), }` after the `enum` keyword
+    fn parse_enum(&mut self) -> Result<Expression, LangError> {
+        let name = match self.next() {
+            Token::IdentifierToken(name) => name,
+            t => return Err(self.error(format!("Expected enum name after 'enum', got: {:?}", t))),
+        };
+
+        self.expect_scope_begin("before enum variants")?;
+
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        loop {
+            let variant = match self.next() {
+                Token::ScopeEndToken => break,
+                Token::IdentifierToken(variant) => variant,
+                t => return Err(self.error(format!("Expected variant name in enum '{}', got: {:?}", name, t))),
+            };
+
+            if variants.iter().any(|(existing, _)| *existing == variant) {
+                return Err(self.error(format!("Duplicate variant '{}' in enum '{}'", variant, name)));
+            }
+
+            let fields = if matches!(self.peek(), Token::OpenParenthesisToken(_)) {
+                self.parse_parameter_list()?.0
+            } else {
+                Vec::new()
+            };
+            variants.push((variant, fields));
+
+            match self.next() {
+                Token::ScopeEndToken => break,
+                t if is_comma(&t) => {},
+                t => return Err(self.error(format!("Expected ',' or '}}' after enum variant, got: {:?}", t))),
+            }
+        }
+
+        Ok(Expression::EnumDeclaration(name, variants))
+    }
+
+    // after a `{`: `{}` or `{ key: ...` starts a map, anything else is a block
+    fn is_map_literal(&self) -> bool {
+        self.is_map_body(0)
+    }
+
+    // `n` is the position of the first token inside the braces
+    fn is_map_body(&self, n: usize) -> bool {
+        let is_colon = matches!(self.peek_nth(n + 1), Token::OperationToken(op) if op == ":");
+        match self.peek_nth(n) {
+            Token::ScopeEndToken => true,
+            Token::IdentifierToken(_) | Token::StringToken(_) => is_colon,
+            _ => false,
+        }
+    }
+
+    // `Name { field: ... }`, only capitalized names so that `if ready {}` stays a condition and a block,
+    // and never directly in a header so that `while Running {}` does too
+    fn is_struct_literal(&self, name: &str) -> bool {
+        !self.in_header && name.starts_with(|c: char| c.is_uppercase()) && self.peek_nth(0) == Token::ScopeBeginToken && self.is_map_body(1)
+    }
+
+    // `{ name: value, "key": value }` after the `{` was consumed
+    fn parse_map_literal(&mut self) -> Result<Expression, LangError> {
+        Ok(Expression::Map(self.parse_map_entries("map literal")?))
+    }
+
+    fn parse_map_entries(&mut self, context: &str) -> Result<Vec<(String, Expression)>, LangError> {
+        let mut entries = Vec::new();
+        loop {
+            let key = match self.next() {
+                Token::ScopeEndToken => break,
+                Token::IdentifierToken(key) | Token::StringToken(key) => key,
+                t => return Err(self.error(format!("Expected a key in {}, got: {:?}", context, t))),
+            };
+
+            match self.next() {
+                Token::OperationToken(op) if op == ":" => {},
+                t => return Err(self.error(format!("Expected ':' after '{}' in {}, got: {:?}", key, context, t))),
+            }
+
+            entries.push((key, self.parse_enclosed(0.0)?));
+
+            match self.next() {
+                Token::ScopeEndToken => break,
+                t if is_comma(&t) => {},
+                t => return Err(self.error(format!("Expected ',' or '}}' in {}, got: {:?}", context, t))),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    // `float`, `[T]` or `Name<A, B>`
+    fn parse_type_annotation(&mut self) -> Result<DataTypeType, LangError> {
+        match self.next() {
+            Token::ArrayBegin => {
+                let element = self.parse_type_annotation()?;
+                match self.next() {
+                    Token::ArrayEnd => Ok(DataTypeType::array(element)),
+                    t => Err(self.error(format!("Expected ']' in array type, got: {:?}", t))),
+                }
+            },
+            Token::IdentifierToken(name) => {
+                if !matches!(self.peek(), Token::OperationToken(op) if op == "<") {
+                    return Ok(DataTypeType::from_annotation(&name, Vec::new()));
+                }
+
+                self.next();
+                let mut params = Vec::new();
+                loop {
+                    params.push(self.parse_type_annotation()?);
+                    match self.next() {
+                        Token::OperationToken(op) if op == ">" => break,
+                        // `Option<[Map<string, float>]>` is fine, `Option<Map<string, float>>` lexes its end as `>>`
+                        Token::OperationToken(op) if op == ">>" => {
+                            let span = self.current_span;
+                            let rest = Span::new(span.offset + 1, span.line, span.column + 1, 1);
+                            self.tokens.push(SpannedToken { token: Token::OperationToken(">".to_string()), span: rest });
+                            break;
+                        },
+                        t if is_comma(&t) => {},
+                        t => return Err(self.error(format!("Expected ',' or '>' in type parameters, got: {:?}", t))),
+                    }
+                }
+
+                Ok(DataTypeType::from_annotation(&name, params))
+            },
+            // `function` is a keyword, so the type of function values does not lex as an identifier
+            Token::FunctionToken(_) => Ok(DataTypeType::from_annotation("function", Vec::new())),
+            t => Err(self.error(format!("Expected a type, got: {:?}", t))),
+        }
+    }
+
+    // `<T, U>` after the name of a generic class or function
+    fn parse_type_parameters(&mut self) -> Result<Vec<String>, LangError> {
+        if !matches!(self.peek(), Token::OperationToken(op) if op == "<") {
+            return Ok(Vec::new());
+        }
+
+        self.next();
+        let mut type_params: Vec<String> = Vec::new();
+        loop {
+            match self.next() {
+                Token::IdentifierToken(param) if type_params.contains(&param) => {
+                    return Err(self.error(format!("Duplicate type parameter '{}'", param)));
+                },
+                Token::IdentifierToken(param) => type_params.push(param),
+                t => return Err(self.error(format!("Expected a type parameter name, got: {:?}", t))),
+            }
+
+            match self.next() {
+                Token::OperationToken(op) if op == ">" => break,
+                t if is_comma(&t) => {},
+                t => return Err(self.error(format!("Expected ',' or '>' after type parameter, got: {:?}", t))),
+            }
+        }
+
+        Ok(type_params)
+    }
+
+    // `class Name<T> : Base { field: type, static method name(...) { ... } }` after the `class` keyword
+    fn parse_class(&mut self) -> Result<Expression, LangError> {
+        let name = match self.next() {
+            Token::IdentifierToken(name) => name,
+            t => return Err(self.error(format!("Expected class name after 'class', got: {:?}", t))),
+        };
+        let type_params = self.parse_type_parameters()?;
+
+        let base = if matches!(self.peek(), Token::OperationToken(op) if op == ":") {
+            self.next();
+            match self.next() {
+                Token::IdentifierToken(base) => Some(base),
+                t => return Err(self.error(format!("Expected base class name after ':', got: {:?}", t))),
+            }
+        } else {
+            None
+        };
+
+        self.expect_scope_begin("before class body")?;
+
+        let mut fields: Vec<FieldDecl> = Vec::new();
+        let mut methods: Vec<MethodDecl> = Vec::new();
+        loop {
+            match self.peek() {
+                Token::ScopeEndToken => {
+                    self.next();
+                    break;
+                },
+                Token::EndExpressionToken(_) => {
+                    self.next();
+                    continue;
+                },
+                t if is_comma(&t) => {
+                    self.next();
+                    continue;
+                },
+                _ => {},
+            }
+
+            // members are private unless marked otherwise
+            let visibility = match self.peek() {
+                Token::PublicToken(_) | Token::PrivateToken(_) | Token::ProtectedToken(_) => visibility_of(&self.next()),
+                _ => Visibility::Private,
+            };
+
+            // `static` and `method` are only keywords in front of a method, `method: float` is still a field
+            let is_keyword = |this: &Self, word: &str| {
+                matches!(this.peek_nth(0), Token::IdentifierToken(w) if w == word) && !matches!(this.peek_nth(1), Token::OperationToken(op) if op == ":")
+            };
+
+            let is_static = is_keyword(self, "static");
+            if is_static {
+                self.next();
+            }
+
+            if is_keyword(self, "method") || matches!(self.peek(), Token::FunctionToken(_)) {
+                self.next();
+                let method_name = match self.next() {
+                    Token::IdentifierToken(method_name) => method_name,
+                    t => return Err(self.error(format!("Expected method name in class '{}', got: {:?}", name, t))),
+                };
+
+                if methods.iter().any(|method| method.name == method_name) {
+                    return Err(self.error(format!("Duplicate method '{}' in class '{}'", method_name, name)));
+                }
+
+                let type_params = self.parse_type_parameters()?;
+                let (params, mut signature) = self.parse_parameter_list()?;
+                signature.type_params = type_params;
+                self.expect_scope_begin("before method body")?;
+                let body = self.parse_block();
+                self.expect_scope_end("after method body")?;
+
+                methods.push(MethodDecl { name: method_name, params, signature, body, is_static, visibility });
+                continue;
+            }
+
+            let field_name = match self.next() {
+                Token::IdentifierToken(field_name) if !is_static => field_name,
+                t => return Err(self.error(format!("Expected field or method in class '{}', got: {:?}", name, t))),
+            };
+
+            if fields.iter().any(|field| field.name == field_name) {
+                return Err(self.error(format!("Duplicate field '{}' in class '{}'", field_name, name)));
+            }
+
+            let annotation = self.parse_annotation(":")?;
+            fields.push(FieldDecl { name: field_name, annotation, visibility });
+        }
+
+        Ok(Expression::ClassDeclaration(ClassDecl { name, type_params, base, fields, methods }))
+    }
+
+    // `target[index]` or `target[start..end]` with either bound optional, after the `[` was consumed.
+    // bounds are parsed above `..` so it reads as the range, concatenation or anything looser inside needs parentheses
+    fn parse_index(&mut self, target: Expression) -> Result<Expression, LangError> {
+        let is_range = |token: &Token| matches!(token, Token::OperationToken(op) if op == "..");
+        let min_bp = 0.997;
+
+        let start = if is_range(&self.peek()) { None } else { Some(Box::new(self.parse_enclosed(min_bp)?)) };
+
+        let expression = match start {
+            Some(index) if !is_range(&self.peek()) => Expression::Index(Box::new(target), index),
+            start => {
+                self.next(); // `..`
+                let end = if self.peek() == Token::ArrayEnd { None } else { Some(Box::new(self.parse_enclosed(min_bp)?)) };
+                Expression::Slice(Box::new(target), start, end)
+            },
+        };
+
+        match self.next() {
+            Token::ArrayEnd => Ok(expression),
+            tok => Err(self.error(format!("Expected ']' after index, got: {:?}", tok))),
+        }
+    }
+
+    // parses a token list that was lexed out of a larger token, like the `{expr}` of a string
+    fn parse_embedded(&mut self, mut tokens: Vec<SpannedToken>) -> Result<Expression, LangError> {
+        tokens.reverse();
+        let outer_tokens = std::mem::replace(&mut self.tokens, tokens);
+
+        let result = self.parse_expression(0.0);
+        let leftover = self.tokens.last().cloned();
+        self.tokens = outer_tokens;
+
+        let expr = result?;
+        if let Some(spanned) = leftover {
+            return Err(LangError::at(format!("Unexpected token in string interpolation: {:?}", spanned.token), spanned.span));
+        }
+
+        Ok(expr)
+    }
+
+    fn number_literal(&self, text: &str) -> Result<Expression, LangError> {
+        match text.parse::<f32>() {
+            Ok(number) => Ok(Expression::Literal(Literal::Number(number))),
+            Err(_) => Err(self.error(format!("Invalid numeric literal '{}'", text))),
+        }
+    }
+
+    // `(a, b: float) -> float` after a function name, the return type is optional
+    fn parse_parameter_list(&mut self) -> Result<(Vec<String>, Signature), LangError> {
+        match self.next() {
+            Token::OpenParenthesisToken(_) => {},
+            t => return Err(self.error(format!("Expected '(' before function parameters, got: {:?}", t))),
+        }
+
+        self.parse_parameters()
+    }
+
+    // after a '(': only names, commas and annotations up to the matching ')' followed by `=>` or `->`
+    fn is_arrow_function(&self) -> bool {
+        let mut n = 0;
+        loop {
+            match self.peek_nth(n) {
+                Token::CloseParenthesisToken(_) => {
+                    return matches!(self.peek_nth(n + 1), Token::OperationToken(op) if op == "=>" || op == "->");
+                },
+                Token::IdentifierToken(_) | Token::FunctionToken(_) | Token::ArrayBegin | Token::ArrayEnd => {},
+                Token::OperationToken(op) if matches!(op.as_str(), ":" | "<" | ">") => {},
+                t if is_comma(&t) => {},
+                _ => return false,
+            }
+            n += 1;
+        }
+    }
+
+    // parameters up to the closing ')' and the return type after it, the opening '(' is already consumed
+    fn parse_parameters(&mut self) -> Result<(Vec<String>, Signature), LangError> {
+        let mut params = Vec::new();
+        let mut signature = Signature::default();
+        loop {
+            match self.next() {
+                Token::CloseParenthesisToken(_) if params.is_empty() => break,
+                Token::IdentifierToken(param) => params.push(param),
+                t => return Err(self.error(format!("Expected parameter name, got: {:?}", t))),
+            }
+            signature.params.push(self.parse_annotation(":")?);
+
+            match self.next() {
+                Token::CloseParenthesisToken(_) => break,
+                t if is_comma(&t) => {},
+                t => return Err(self.error(format!("Expected ',' or ')' after parameter, got: {:?}", t))),
+            }
+        }
+
+        signature.returns = self.parse_annotation("->")?;
+        Ok((params, signature))
+    }
+
+    // a type after `separator` if it comes next
+    fn parse_annotation(&mut self, separator: &str) -> Result<Option<DataTypeType>, LangError> {
+        if !matches!(self.peek(), Token::OperationToken(op) if op == separator) {
+            return Ok(None);
+        }
+
+        self.next();
+        Ok(Some(self.parse_type_annotation()?))
+    }
+
+    // arguments of a call, the opening '(' is already consumed
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expression>, LangError> {
+        let mut args = Vec::new();
+        loop {
+            if matches!(self.peek(), Token::CloseParenthesisToken(_)) {
+                self.next();
+                break;
+            }
+
+            args.push(self.parse_enclosed(0.0)?);
+
+            match self.next() {
+                Token::CloseParenthesisToken(_) => break,
+                t if is_comma(&t) => {},
+                t => return Err(self.error(format!("Expected ',' or ')' in call arguments, got: {:?}", t))),
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn expect_scope_begin(&mut self, context: &str) -> Result<(), LangError> {
+        match self.next() {
+            Token::ScopeBeginToken => Ok(()),
+            t => Err(self.error(format!("Expected '{{' {}, got: {:?}", context, t))),
+        }
+    }
+
+    fn expect_scope_end(&mut self, context: &str) -> Result<(), LangError> {
+        match self.next() {
+            Token::ScopeEndToken => Ok(()),
+            t => Err(self.error(format!("Expected '}}' or 'end' {}, got: {:?}", context, t))),
+        }
+    }
+
+    fn expect_then(&mut self, context: &str) -> Result<(), LangError> {
+        match self.next() {
+            t if is_then_token(&t) => Ok(()),
+            t => Err(self.error(format!("Expected '{{' or 'then' {}, got: {:?}", context, t))),
+        }
+    }
+
+    // a block ends at `}`/`end`, or at `elseif`/`else` for lua style if chains. syntax errors
+    // inside are recorded and the block keeps going with the next statement
     pub fn parse_block(&mut self) -> Expression {
         let mut expressions = Vec::new();
         
         loop {
             match self.peek() {
-                Token::ScopeEndToken | Token::EofToken => break,
+                Token::ScopeEndToken | Token::ElseIfToken(_) | Token::ElseToken(_) | Token::EofToken => break,
                 Token::EndExpressionToken(_) => {
                     self.next();
                     continue;
                 }
                 _ => {
-                    expressions.push(self.parse_expression(0.0).unwrap());
-                }
+                    let statement_start = self.peek_span();
+                    match self.parse_statement() {
+                        Ok(expr) => expressions.push((expr, statement_start.to(&self.current_span))),
+                        Err(err) => {
+                            self.diagnostics.push(err);
+                            self.synchronize(true);
+                        }
+                    }
+                },
             }
         }
         
         Expression::Block(expressions)
     }
 
+    // compound assignments and `++`/`--` are statements, only the outermost expression of one may use them
+    fn parse_statement(&mut self) -> Result<Expression, LangError> {
+        let outer_depth = std::mem::take(&mut self.expression_depth);
+        let outer_header = std::mem::take(&mut self.in_header);
+        let result = self.parse_expression(0.0);
+        self.expression_depth = outer_depth;
+        self.in_header = outer_header;
+
+        result
+    }
+
+    // the condition of an if/elseif/while or a bound of a for loop
+    fn parse_header(&mut self) -> Result<Expression, LangError> {
+        let outer = std::mem::replace(&mut self.in_header, true);
+        let result = self.parse_expression(0.0);
+        self.in_header = outer;
+
+        result
+    }
+
+    // an expression inside brackets or braces, where struct literals are allowed again
+    fn parse_enclosed(&mut self, min_bp: f32) -> Result<Expression, LangError> {
+        let outer = std::mem::take(&mut self.in_header);
+        let result = self.parse_expression(min_bp);
+        self.in_header = outer;
+
+        result
+    }
+
     pub fn parse_expression(&mut self, min_bp: f32) -> Result<Expression, LangError> {
+        self.expression_depth += 1;
+        let result = self.parse_operand_chain(min_bp);
+        self.expression_depth -= 1;
+
+        result
+    }
+
+    fn parse_operand_chain(&mut self, min_bp: f32) -> Result<Expression, LangError> {
         let mut lvalue = match self.next() {
             Token::EndExpressionToken(_) => {
-                return self.parse_expression(min_bp);
+                return Err(self.error("Expected expression, got ';'".to_string()));
             }
-            Token::LetToken(_, cur_line) => {
-                self.current_line = cur_line as usize;
+            Token::OperationToken(op) if prefix_binding_power(&op).is_some() => {
+                let r_bp = prefix_binding_power(&op).unwrap_or_default();
+                let operand = self.parse_expression(r_bp)?;
+                let op = if op == "not" { "!".to_string() } else { op };
+
+                Expression::Unary(op, Box::new(operand))
+            },
+            Token::LetToken(_) => {
                 match self.next() {
-                    Token::IdentifierToken(var_name, cur_line) => {
-                        self.current_line = cur_line as usize;
-                        Expression::Declaration(var_name)
-                    },
-                    t => return Err(LangError::new(format!("Expected identifier after 'let', got: {:?}", t))),
+                    Token::IdentifierToken(var_name) => Expression::Declaration(var_name, self.parse_annotation(":")?),
+                    t => return Err(self.error(format!("Expected identifier after 'let', got: {:?}", t))),
+                }
+            },
+            Token::EnumToken(_) => self.parse_enum()?,
+            Token::ClassToken(_) => self.parse_class()?,
+            Token::UsingToken(_) => Expression::Using(Box::new(self.parse_expression(0.0)?)),
+            modifier @ (Token::PublicToken(_) | Token::PrivateToken(_) | Token::ProtectedToken(_)) => {
+                let visibility = visibility_of(&modifier);
+                let declaration = self.parse_expression(0.0)?;
+                if declaration.declared_name().is_none() {
+                    return Err(self.error(format!("'{}' can only be applied to a declaration, got: {}", visibility, declaration)));
+                }
+
+                Expression::Modified(visibility, Box::new(declaration))
+            },
+            Token::ImportToken(_) => self.parse_import()?,
+            Token::ModuleToken(_) => {
+                let name = match self.next() {
+                    Token::IdentifierToken(name) => name,
+                    t => return Err(self.error(format!("Expected module name after 'module', got: {:?}", t))),
+                };
+
+                self.expect_scope_begin("before module body")?;
+                let Expression::Block(body) = self.parse_block() else { unreachable!() };
+                self.expect_scope_end("after module body")?;
+
+                Expression::ModuleDeclaration(name, body)
+            },
+            Token::FunctionToken(_) if matches!(self.peek(), Token::OpenParenthesisToken(_)) => {
+                let (params, signature) = self.parse_parameter_list()?;
+
+                // `function(x) { ... }` or the Lua form `function(x) ... end`
+                if self.peek() == Token::ScopeBeginToken {
+                    self.next();
                 }
+                let body = self.parse_block();
+                self.expect_scope_end("after function body")?;
+
+                Expression::Closure(params, Box::new(body), signature)
             },
             Token::FunctionToken(_) => {
                 match self.next() {
-                    Token::IdentifierToken(fn_signature, cur_line) => {
-                        self.current_line = cur_line as usize;
-                        let (fn_name, params) = parse_function_signature(&fn_signature)?;
+                    Token::IdentifierToken(fn_name) => {
+                        let type_params = self.parse_type_parameters()?;
+                        let (params, mut signature) = self.parse_parameter_list()?;
+                        signature.type_params = type_params;
                         
-                        assert_eq!(self.next(), Token::ScopeBeginToken);
+                        self.expect_scope_begin("before function body")?;
                         let body = self.parse_block();
-                        assert_eq!(self.next(), Token::ScopeEndToken);
+                        self.expect_scope_end("after function body")?;
                         
-                        Expression::FunctionDeclaration(fn_name, params, Box::new(body))
+                        Expression::FunctionDeclaration(fn_name, params, Box::new(body), signature)
                     }
-                    t => return Err(LangError::new(format!("Expected function signature after 'function', got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected function name after 'function', got: {:?}", t))),
                 }
             },
             Token::ArrayBegin => {
                 let mut elements: Vec<Expression> = Vec::new();
                 loop {
-                    match self.peek() {
-                        Token::ArrayEnd => {
-                            self.next();
-                            break;
-                        },
-                        Token::IdentifierToken(tok, cur_line) if tok == "," => {
-                            self.current_line = cur_line as usize;
-                            self.next();
-                            continue;
-                        },
-                        Token::IdentifierToken(mut tok, cur_line) | Token::NumericToken(mut tok, cur_line) => {
-                            self.current_line = cur_line as usize;
-                            if tok.ends_with(",") {
-                                self.next();
-                                tok.remove(tok.len() - 1);
-                                elements.push(Expression::Atom(tok))
-                            } else {
-                                self.next();
-                                elements.push(Expression::Atom(tok))
-                            }
-                        },
-                        tok => return Err(
-                            LangError::new(format!("Invalid token between array declaration: {}", tok))
-                        )
+                    if self.peek() == Token::ArrayEnd {
+                        self.next();
+                        break;
                     }
-                } 
-                
+
+                    elements.push(self.parse_enclosed(0.0)?);
+
+                    match self.next() {
+                        Token::ArrayEnd => break,
+                        t if is_comma(&t) => {},
+                        t => return Err(self.error(format!("Expected ',' or ']' in array literal, got: {:?}", t))),
+                    }
+                }
+
                 Expression::Array(elements)
             }
             Token::IfToken(_) => {
-                let condition = self.parse_expression(0.0)?;
+                let condition = self.parse_header()?;
                 
-                match self.next() {
-                    Token::ScopeBeginToken | Token::ThenToken => {}
-                    t => return Err(LangError::new(format!("Expected '{{' after 'if', got: {:?}", t))),
-                }
+                self.expect_then("after 'if' condition")?;
                 let then_body = self.parse_block();
-                if !matches!(self.next(), Token::ScopeEndToken | Token::ElseIfToken(_)) {
-                    return Err(
-                        LangError::new("Expected '}}' or 'else if' after 'if' block".to_string())
-                    )
-                }
 
                 let mut elseif_branches = Vec::new();
+                let mut else_body = None;
                 
-                while matches!(self.peek(), Token::ElseIfToken(_)) {
-                    self.next();
-                    
-                    let elseif_condition = self.parse_expression(0.0)?;
-                    
-                    assert_eq!(true, is_then_token(&self.next()));
-                    let elseif_body = self.parse_block();
-                    assert_eq!(self.next(), Token::ScopeEndToken);
-                    
-                    elseif_branches.push((elseif_condition, elseif_body));
+                // brace style closes every branch with '}', lua style only closes the chain with `end`
+                loop {
+                    match self.next() {
+                        Token::ScopeEndToken => {
+                            if !matches!(self.peek(), Token::ElseIfToken(_) | Token::ElseToken(_)) || else_body.is_some() {
+                                break;
+                            }
+                        },
+                        Token::ElseIfToken(_) if else_body.is_none() => {
+                            let elseif_condition = self.parse_header()?;
+                            self.expect_then("after 'elseif' condition")?;
+                            elseif_branches.push((elseif_condition, self.parse_block()));
+                        },
+                        Token::ElseToken(_) if else_body.is_none() && matches!(self.peek(), Token::IfToken(_)) => {
+                            self.next();
+
+                            let elseif_condition = self.parse_header()?;
+                            self.expect_then("after 'else if' condition")?;
+                            elseif_branches.push((elseif_condition, self.parse_block()));
+                        },
+                        Token::ElseToken(_) if else_body.is_none() => {
+                            if is_then_token(&self.peek()) {
+                                self.next();
+                            }
+                            else_body = Some(Box::new(self.parse_block()));
+                        },
+                        t => return Err(self.error(format!("Expected '}}', 'elseif' or 'else' after if branch, got: {:?}", t))),
+                    }
                 }
                 
-                let else_body = if matches!(self.peek(), Token::ElseToken(_)) {
-                    self.next();
-                    
-                    assert_eq!(true, is_then_token(&self.next()));
-                    let body = self.parse_block();
-                    assert_eq!(self.next(), Token::ScopeEndToken);
-                    
-                    Some(Box::new(body))
-                } else {
-                    None
-                };
-                
                 Expression::If(Box::new(condition), Box::new(then_body), elseif_branches, else_body)
             },
+            Token::ScopeBeginToken if self.is_map_literal() => self.parse_map_literal()?,
             Token::ScopeBeginToken => {
                 let block = self.parse_block();
-                assert_eq!(self.next(), Token::ScopeEndToken);
+                self.expect_scope_end("after block")?;
                 block
             },
-            Token::BoolToken(val) => Expression::Atom(val),
-            Token::StringToken(val) => Expression::Atom(val),
-            Token::IdentifierToken(var_name, cur_line) => {
-                self.current_line = cur_line as usize;
-                if is_function_call(&var_name) {
-                    let (fn_name, arg_expressions) = parse_function_call_with_program(&var_name)?;
-                    Expression::FunctionCall(fn_name, Box::new(arg_expressions))
-                } else {
-                    Expression::Atom(var_name)
+            Token::BoolToken(val) => Expression::Literal(Literal::Bool(val == "true")),
+            Token::StringToken(val) => Expression::Literal(Literal::String(val)),
+            Token::InterpolatedStringToken(parts) => {
+                let mut segments = Vec::new();
+                for part in parts {
+                    match part {
+                        StringPart::Text(text) => segments.push(Expression::Literal(Literal::String(text))),
+                        StringPart::Code(code) => segments.push(self.parse_embedded(code)?),
+                    }
                 }
+
+                Expression::Interpolation(segments)
             },
-            Token::NumericToken(var_name, cur_line) => {
-                self.current_line = cur_line as usize;
-                Expression::Atom(var_name)
+            Token::IdentifierToken(class_name) if self.is_struct_literal(&class_name) => {
+                self.next(); // `{`
+                Expression::StructLiteral(class_name, self.parse_map_entries("struct literal")?)
+            },
+            Token::IdentifierToken(var_name) => Expression::Identifier(var_name),
+            Token::NumericToken(number) => self.number_literal(&number)?,
+            Token::OpenParenthesisToken(_) if self.is_arrow_function() => {
+                let (params, signature) = self.parse_parameters()?;
+                match self.next() {
+                    Token::OperationToken(op) if op == "=>" => {},
+                    t => return Err(self.error(format!("Expected '=>' after the parameters, got: {:?}", t))),
+                }
+
+                let body = if self.peek() == Token::ScopeBeginToken {
+                    self.next();
+                    let block = self.parse_block();
+                    self.expect_scope_end("after function body")?;
+                    block
+                } else {
+                    self.parse_expression(0.0)?
+                };
+
+                Expression::Closure(params, Box::new(body), signature)
             },
             Token::OpenParenthesisToken(_) => {
-                let last_expr = self.parse_expression(0.0);
-                assert_eq!(self.next(), Token::CloseParenthesisToken(")".to_string()));
-                last_expr.unwrap()
+                let last_expr = self.parse_enclosed(0.0)?;
+                match self.next() {
+                    Token::CloseParenthesisToken(_) => last_expr,
+                    t => return Err(self.error(format!("Expected ')', got: {:?}", t))),
+                }
+            },
+            Token::ReturnToken(_) if matches!(self.peek(), Token::EndExpressionToken(_) | Token::ScopeEndToken) => {
+                Expression::Return(Box::new(Expression::Block(vec![])))
             },
             Token::ReturnToken(_) => {
                 let result = self.parse_expression(0.0);
@@ -506,61 +1206,55 @@ impl Program {
             
             Token::ForToken(_) => {
                 let var_name = match self.next() {
-                    Token::IdentifierToken(name, cur_line) => {self.current_line = cur_line as usize; name},
-                    t => return Err(LangError::new(format!("Expected variable name after 'for', got: {:?}", t))),
+                    Token::IdentifierToken(name) => name,
+                    t => return Err(self.error(format!("Expected variable name after 'for', got: {:?}", t))),
                 };
                 
                 match self.next() {
-                    Token::EqualToken(op) | Token::OperationToken(op, _) if op == "=" => {},
-                    t => return Err(LangError::new(format!("Expected '=' after for variable, got: {:?}", t))),
+                    Token::EqualToken(op) | Token::OperationToken(op) if op == "=" => {},
+                    t => return Err(self.error(format!("Expected '=' after for variable, got: {:?}", t))),
                 }
                 
-                let start = self.parse_expression(0.0)?;
+                let start = self.parse_header()?;
                 
                 match self.next() {
-                    Token::IdentifierToken(ref s, cur_line) if s == "," => {self.current_line = cur_line as usize},
-                    Token::OperationToken(ref s, _) if s == "," => {},
-                    t => return Err(LangError::new(format!("Expected ',' after for start value, got: {:?}", t))),
+                    Token::IdentifierToken(ref s) | Token::OperationToken(ref s) if s == "," => {},
+                    t => return Err(self.error(format!("Expected ',' after for start value, got: {:?}", t))),
                 }
                 
-                let end = self.parse_expression(0.0)?;
+                let end = self.parse_header()?;
                 
-                let step = match self.peek() {
-                    Token::IdentifierToken(ref s, _) | Token::OperationToken(ref s, _) => {
-                        if s == "," {
-                            None
-                        } else {
-                            self.next();
-                            Some(Box::new(self.parse_expression(0.0)?))
-                        }
-                    },
-                    _ => None,
+                let step = if is_comma(&self.peek()) {
+                    self.next();
+                    Some(Box::new(self.parse_header()?))
+                } else {
+                    None
                 };
                 
                 match self.next() {
                     Token::ScopeBeginToken => {},
-                    t => return Err(LangError::new(format!("Expected '{{' after for parameters, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '{{' after for parameters, got: {:?}", t))),
                 }
                 
                 let body = self.parse_block();
                 match self.next() {
                     Token::ScopeEndToken => {},
-                    t => return Err(LangError::new(format!("Expected '}}' after for body, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '}}' after for body, got: {:?}", t))),
                 }
                 
                 Expression::ForLoop(var_name, Box::new(start), Box::new(end), step, Box::new(body))
             },
             Token::WhileToken(_) => {
-                let condition = self.parse_expression(0.0)?;
+                let condition = self.parse_header()?;
                 match self.next() {
                     Token::ScopeBeginToken => {},
-                    t => return Err(LangError::new(format!("Expected '{{' after while condition, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '{{' after while condition, got: {:?}", t))),
                 }
                 
                 let body = self.parse_block();
                 match self.next() {
                     Token::ScopeEndToken => {},
-                    t => return Err(LangError::new(format!("Expected '}}' after while body, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '}}' after while body, got: {:?}", t))),
                 }
                 
                 Expression::WhileLoop(Box::new(condition), Box::new(body))
@@ -569,13 +1263,13 @@ impl Program {
             Token::LoopToken(_) => {
                 match self.next() {
                     Token::ScopeBeginToken => {},
-                    t => return Err(LangError::new(format!("Expected '{{' after loop, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '{{' after loop, got: {:?}", t))),
                 }
                 
                 let body = self.parse_block();
                 match self.next() {
                     Token::ScopeEndToken => {},
-                    t => return Err(LangError::new(format!("Expected '}}' after loop body, got: {:?}", t))),
+                    t => return Err(self.error(format!("Expected '}}' after loop body, got: {:?}", t))),
                 }
                 
                 Expression::InfiniteLoop(Box::new(body))
@@ -585,7 +1279,7 @@ impl Program {
             
             Token::ContinueToken(_) => Expression::Continue,
             t => return Err(
-                LangError::new(format!("[Tokens]: Unknown reference to \x1b[1;32m\"{:?}\"\x1b[0m", t))
+                self.error(format!("[Tokens]: Unknown reference to \x1b[1;32m\"{:?}\"\x1b[0m", t))
             ),
         };
 
@@ -593,18 +1287,70 @@ impl Program {
             let op = match self.peek() {
                 Token::EofToken | Token::EndExpressionToken(_) => break,
                 Token::CloseParenthesisToken(_) => break,
-                Token::OperationToken(opv, _) 
-                | Token::EqualToken(opv)
-                | Token::CompareToken(opv) => opv,
+                Token::EqualToken(_) => "=".to_string(), // `be`
+                Token::OpenParenthesisToken(opv) => opv, // call
+                Token::ArrayBegin => "[".to_string(), // index
+                Token::AndToken(_) => "&&".to_string(), // `and`
+                Token::OrToken(_) => "||".to_string(), // `or`
+                Token::OperationToken(opv) 
+                | Token::CompareToken(opv)
+                | Token::NotEqualToken(opv)
+                | Token::GreaterEqualToken(opv)
+                | Token::LessEqualToken(opv)
+                | Token::NamespaceAccessToken(opv) => opv,
                 _ => break,
             };
 
-            let (l_bp, r_bp) = operator_binding_power(&op);
+            let Some((l_bp, r_bp)) = operator_binding_power(&op) else {
+                break;
+            };
             if l_bp < min_bp {
                 break;
             }
 
             self.next();
+            if op == "(" {
+                let args = self.parse_call_arguments()?;
+                lvalue = Expression::FunctionCall(Box::new(lvalue), args);
+                continue;
+            }
+
+            if op == "[" {
+                lvalue = self.parse_index(lvalue)?;
+                continue;
+            }
+
+            if op == "." || op == "::" {
+                lvalue = match self.next() {
+                    Token::IdentifierToken(member) => Expression::Member(Box::new(lvalue), member),
+                    t => return Err(self.error(format!("Expected member name after '.', got: {:?}", t))),
+                };
+                continue;
+            }
+
+            if op == "=" && !matches!(lvalue, Expression::Identifier(_) | Expression::Declaration(..) | Expression::Index(..) | Expression::Member(..)) {
+                return Err(self.error(format!("Invalid assignment target: {}", lvalue)));
+            }
+
+            // `x += e` and `x++` store `x + e` and `x + 1` back into `x`, the target is evaluated once
+            if let Some(arithmetic_op) = compound_operator(&op) {
+                if self.expression_depth > 1 {
+                    return Err(self.error(format!("'{}' is a statement and cannot be used inside an expression", op)));
+                }
+                if !matches!(lvalue, Expression::Identifier(_) | Expression::Index(..) | Expression::Member(..)) {
+                    return Err(self.error(format!("Invalid assignment target: {}", lvalue)));
+                }
+
+                let rvalue = if op == "++" || op == "--" {
+                    Expression::Literal(Literal::Number(1.0))
+                } else {
+                    self.parse_expression(r_bp)?
+                };
+
+                lvalue = Expression::CompoundAssignment(arithmetic_op.to_string(), Box::new(lvalue), Box::new(rvalue));
+                continue;
+            }
+
             match self.parse_expression(r_bp) {
                 Ok(rvalue) => lvalue = Expression::Operation(op, vec![lvalue, rvalue]),
                 Err(err) => return Err(err),
@@ -617,4 +1363,85 @@ impl Program {
     pub fn is_token_ending(token: &SplitToken) -> bool {
         token.value == LINE_END_TOKEN || token.value == " "
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the line and message of every syntax error in `source`
+    fn syntax_errors(source: &str) -> Vec<(usize, String)> {
+        let mut program = Program::new();
+        program.tokenize(source).unwrap();
+        program.parse();
+
+        program.diagnostics.into_iter().map(|err| (err.span.map_or(0, |span| span.line), err.message)).collect()
+    }
+
+    fn lines(source: &str) -> Vec<usize> {
+        syntax_errors(source).into_iter().map(|(line, _)| line).collect()
+    }
+
+    #[test]
+    fn reports_consecutive_broken_statements() {
+        assert_eq!(lines("let a = ;\nlet b = ;\nlet c = ;\nlet d = ;"), [1, 2, 3, 4]);
+        assert_eq!(syntax_errors("let a = ;")[0].1, "Expected expression, got ';'");
+    }
+
+    #[test]
+    fn recovers_at_the_end_of_a_block() {
+        assert_eq!(lines("if true { let a = }\nlet b = ;\nlet c = 1;\nlet d = ;"), [1, 2, 4]);
+        assert_eq!(lines("while true do\n  let x = \nend\nlet y = ;"), [3, 4]);
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let source = "function f() {\n    let a = (1;\n    let b = 2;\n    let c = ;\n}\nlet d = [1,;";
+        assert_eq!(lines(source), [2, 4, 6]);
+    }
+
+    fn parsed(source: &str) -> String {
+        let mut program = Program::new();
+        program.tokenize(source).unwrap();
+        let statements = program.parse();
+        assert!(program.diagnostics.is_empty(), "{:?}", program.diagnostics);
+
+        statements.iter().map(|(expr, _)| expr.to_string()).collect::<Vec<_>>().join("; ")
+    }
+
+    #[test]
+    fn concatenation_binds_tighter_than_comparisons() {
+        assert_eq!(parsed("a .. \"!\" == b"), "(== (.. a \"!\") b)");
+        assert_eq!(parsed("x .. 1 + 2"), "(.. x (+ 1 2))");
+        assert_eq!(parsed("p && a .. b < c"), "(&& p (< (.. a b) c))");
+        // inside brackets `..` is the slice range
+        assert_eq!(parsed("xs[1 + 1..n]"), "xs[(+ 1 1)..n]");
+    }
+
+    #[test]
+    fn capitalized_names_in_headers_are_not_struct_literals() {
+        assert!(syntax_errors("let N = 2;\nfor i = 1, N {}\nif Debug {} elseif Verbose {}\nwhile Running {}").is_empty());
+        assert!(syntax_errors("if (Point { x: 1 }).x == 1 {}\nwhile check(Point { x: 1 }) {}\nfor i = 1, [Point { x: 1 }][0].x {}").is_empty());
+
+        let mut program = Program::new();
+        program.tokenize("let e = Empty {};\nlet p = Point { x: 1 };").unwrap();
+        let statements = program.parse();
+        assert!(program.diagnostics.is_empty());
+        for (statement, _) in statements {
+            let Expression::Operation(_, operands) = statement else { panic!("expected an assignment, got {}", statement) };
+            assert!(matches!(operands.last(), Some(Expression::StructLiteral(..))), "{:?}", operands.last());
+        }
+    }
+
+    #[test]
+    fn compound_assignments_are_statements() {
+        assert!(syntax_errors("let x = 1;\nx += 1;\nx++;\nx--;\nlet a = [1];\na[0] *= 2;").is_empty());
+        assert_eq!(syntax_errors("let y = (x += 1);"), [(1, "'+=' is a statement and cannot be used inside an expression".to_string())]);
+        assert_eq!(syntax_errors("print(x++);")[0].1, "'++' is a statement and cannot be used inside an expression");
+    }
+
+    #[test]
+    fn valid_statements_parse_cleanly() {
+        assert!(syntax_errors("let a = 1;\nif a > 0 { print(a) } else { print(-a) }\nwhile false do end").is_empty());
+    }
+}
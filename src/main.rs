@@ -1,11 +1,13 @@
 use lang::files::file;
 use lang::language;
 use std::env;
+use std::path::PathBuf;
 
 struct ProgramParams {
     tokenize: bool,
     debug: bool,
     is_source: bool,
+    search_paths: Vec<PathBuf>,
 }
 
 fn parse_params(args: &Vec<String>, params: &mut ProgramParams) {
@@ -14,22 +16,32 @@ fn parse_params(args: &Vec<String>, params: &mut ProgramParams) {
             "-t" | "--tokenize" => params.tokenize = true,
             "-d" | "--debug" => params.debug = true,
             "-s" | "--isSource" => params.is_source = true,
+            path if path.starts_with("--path=") => params.search_paths.push(PathBuf::from(&path["--path=".len()..])),
             _ => println!("Unknown argument: {}", arg),
         }
     }
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // `check file.nx` reports problems without running the program
+    let checking = args.len() > 2 && args[1] == "check";
+    if checking {
+        args.remove(1);
+    }
     let mut params = ProgramParams {
         tokenize: false,
         debug: false,
         is_source: false,
-        
+        // `--path=dir` entries are searched before the ones in NEXEN_PATH
+        search_paths: Vec::new(),
     };
     
     if args.len() > 1 {
         parse_params(&args, &mut params);
+        if let Some(nexen_path) = env::var_os("NEXEN_PATH") {
+            params.search_paths.extend(env::split_paths(&nexen_path));
+        }
         let file_path = &args[1];
 
         let using_source = if params.is_source {
@@ -47,14 +59,31 @@ fn main() {
                 if let Err(err) = language::tokenize(source) {
                     println!("[Interpreter] when tokenizing {}: \n\n> {}", file_path, err)
                 }
+            } else if checking {
+                let entry_file = if params.is_source { None } else { Some(file_path.as_str()) };
+                match language::check(source, entry_file, params.search_paths) {
+                    Ok(_) => println!("\x1b[1;32m[Nexen]\x1b[0m No problems found in {}", file_path),
+                    Err(errors) => {
+                        println!("[Checker] when checking {}: ", file_path);
+                        for err in errors {
+                            println!("\n{}", err);
+                        }
+                    }
+                }
             } else {
-                match language::interpret(source) {
+                let entry_file = if params.is_source { None } else { Some(file_path.as_str()) };
+                match language::interpret(source, entry_file, params.search_paths) {
                     Ok(program_exec_time) => {
                         if params.debug {
                             println!("\r\x1b[1;32m[Nexen]\x1b[0m Program finished\n-> Execution time: \x1b[1;31m[{:?}]\x1b[0m", program_exec_time);
                         }
                     }
-                    Err(err) => println!("[Interpreter] when executing {}: \n\n{}", file_path, err)
+                    Err(errors) => {
+                        println!("[Interpreter] when executing {}: ", file_path);
+                        for err in errors {
+                            println!("\n{}", err);
+                        }
+                    }
                 }
             }
         } else {